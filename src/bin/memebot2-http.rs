@@ -8,6 +8,10 @@ use rocket::fairing::{Fairing, Info, Kind};
 use rocket::http::Header;
 use std::error::Error;
 
+#[path = "../config.rs"]
+#[allow(dead_code)]
+mod config;
+
 
 pub struct CORS;
 
@@ -33,10 +37,22 @@ struct Meme {
     text: String,
 }
 
+// tables are only created once a guild first uses them
+fn exists(conn: &Connection, table: &str) -> rusqlite::Result<bool> {
+    conn.query_row(
+        "SELECT count(*) FROM sqlite_master WHERE type='table' AND name=?",
+        params![table],
+        |row| row.get::<usize, i64>(0),
+    )
+    .map(|x| x > 0)
+}
+
+// nsfw memes are left out here and in collections as there's no way to tell
+// who is asking
 #[get("/<guild>")]
 fn list(guild: u64) -> Result<String, Box<dyn Error>> {
-    let conn = Connection::open("data.db")?; //FIXME: read config.rs
-    let mut stmt = conn.prepare(&format!("SELECT * FROM x{}_memes", guild))?;
+    let conn = Connection::open(config::DB_PATH)?;
+    let mut stmt = conn.prepare(&format!("SELECT id, time, text FROM x{}_memes WHERE NOT coalesce(nsfw, 0)", guild))?;
     let iter = stmt.query_map(params![], |row| {
         Ok(Meme {
             id: row.get(0)?,
@@ -50,10 +66,50 @@ fn list(guild: u64) -> Result<String, Box<dyn Error>> {
         .map(|x| format!("{} {} {}\n", x.id, x.time, x.text))
         .collect())
 }
+
+#[get("/<guild>/collections")]
+fn collections(guild: u64) -> Result<String, Box<dyn Error>> {
+    let conn = Connection::open(config::DB_PATH)?;
+    if !exists(&conn, &format!("x{}_collections", guild))? {
+        return Ok(String::new());
+    }
+    let mut stmt = conn.prepare(&format!("SELECT name FROM x{}_collections ORDER BY name", guild))?;
+    let iter = stmt.query_map(params![], |row| row.get::<usize, String>(0))?;
+
+    Ok(iter
+        .filter_map(|x| x.ok())
+        .map(|x| format!("{}\n", x))
+        .collect())
+}
+
+#[get("/<guild>/collections/<name>")]
+fn collection(guild: u64, name: String) -> Result<String, Box<dyn Error>> {
+    let conn = Connection::open(config::DB_PATH)?;
+    if !exists(&conn, &format!("x{}_collection_memes", guild))? {
+        return Ok(String::new());
+    }
+    let mut stmt = conn.prepare(&format!(
+        "SELECT m.id, m.time, m.text FROM x{0}_collection_memes c JOIN x{0}_memes m ON m.id = c.meme WHERE c.name = ? AND NOT coalesce(m.nsfw, 0) ORDER BY c.position",
+        guild
+    ))?;
+    let iter = stmt.query_map(params![name.to_lowercase()], |row| {
+        Ok(Meme {
+            id: row.get(0)?,
+            time: row.get(1).unwrap_or(0),
+            text: row.get(2)?,
+        })
+    })?;
+
+    Ok(iter
+        .filter_map(|x| x.ok())
+        .map(|x| format!("{} {} {}\n", x.id, x.time, x.text))
+        .collect())
+}
+
 fn main() {
     let config = Config::build(Environment::Production)
         .address("127.0.0.1")
         .port(5360)
         .finalize().unwrap();
-    rocket::custom(config).attach(CORS).mount("/", routes![list]).launch();
+    rocket::custom(config).attach(CORS).mount("/", routes![list, collections, collection]).launch();
 }
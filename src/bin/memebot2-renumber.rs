@@ -0,0 +1,33 @@
+//! Does the same as `!memes renumber` for when the bot isn't running.
+//!
+//! usage: memebot2-renumber <guild id> [database]
+
+#[path = "../config.rs"]
+#[allow(dead_code)]
+mod config;
+
+#[path = "../modules/memes/renumber.rs"]
+#[allow(dead_code)]
+mod renumber;
+
+use rusqlite::Connection;
+use std::error::Error;
+
+fn main() -> Result<(), Box<dyn Error>> {
+    let args: Vec<String> = std::env::args().collect();
+    if args.len() < 2 {
+        eprintln!("usage: {} <guild id> [database]", args[0]);
+        std::process::exit(1);
+    }
+    let guild: u64 = args[1].parse()?;
+    let path = args.get(2).map(String::as_str).unwrap_or(config::DB_PATH);
+
+    let mut conn = Connection::open(path)?;
+    let changes = renumber::renumber(&mut conn, guild)?;
+    for (old, new) in changes.iter() {
+        println!("{} -> {}", old, new);
+    }
+    println!("renumbered {} memes", changes.len());
+
+    Ok(())
+}
@@ -1,3 +1,5 @@
 compile_error!("please edit config.rs");
 pub const TOKEN: &str = "";
 pub const DB_PATH: &str = "data.db";
+pub const TEMPLATE_DIR: &str = "templates";
+pub const FONT_PATH: &str = "impact.ttf";
@@ -12,6 +12,7 @@ use serenity::http::Http;
 use serenity::model::channel::Message;
 use serenity::model::prelude::*;
 use std::collections::HashSet;
+use std::sync::atomic::{AtomicBool, Ordering};
 
 #[help]
 async fn help(
@@ -26,8 +27,32 @@ async fn help(
     Ok(())
 }
 
-struct Handler;
-impl EventHandler for Handler {}
+struct Handler {
+    scheduler: AtomicBool,
+}
+
+#[serenity::async_trait]
+impl EventHandler for Handler {
+    async fn cache_ready(&self, ctx: Context, _guilds: Vec<GuildId>) {
+        // the cache is rebuilt on every reconnect, but the scheduler only
+        // needs to be started once
+        if !self.scheduler.swap(true, Ordering::SeqCst) {
+            tokio::spawn(modules::schedule::run(ctx));
+        }
+    }
+
+    async fn channel_pins_update(&self, ctx: Context, pin: ChannelPinsUpdateEvent) {
+        if let Err(x) = modules::memes::pins::pinned(&ctx, &pin).await {
+            println!("error capturing pin in {}: {}", pin.channel_id, x);
+        }
+    }
+
+    async fn message(&self, ctx: Context, msg: Message) {
+        if let Err(x) = modules::memes::inline::expand(&ctx, &msg).await {
+            println!("error expanding memes in {}: {}", msg.id, x);
+        }
+    }
+}
 
 #[hook]
 async fn dispatch_error(ctx: &Context, msg: &Message, error: DispatchError) {
@@ -61,13 +86,23 @@ async fn main() -> Result<(), Box<dyn std::error::Error>> {
     };
 
     let mut client = Client::builder(config::TOKEN)
-        .event_handler(Handler)
+        .event_handler(Handler {
+            scheduler: AtomicBool::new(false),
+        })
         .framework(
             StandardFramework::new()
                 .configure(|c| c.prefix("!").on_mention(Some(bot_id)).owners(owners))
                 .group(&modules::perms::PERMISSIONS_GROUP)
                 .group(&modules::memes::MEMES_GROUP)
+                .group(&modules::memes::MEMETOOLS_GROUP)
+                .group(&modules::memes::queue::QUEUE_GROUP)
+                .group(&modules::memes::game::MEMEGAME_GROUP)
+                .group(&modules::memes::policy::MEMEPOLICY_GROUP)
+                .group(&modules::memes::shared::SHARED_GROUP)
+                .group(&modules::memes::collection::COLLECTION_GROUP)
+                .group(&modules::memeimg::MEMEIMG_GROUP)
                 .group(&modules::roles::ROLES_GROUP)
+                .group(&modules::settings::SETTINGS_GROUP)
                 .on_dispatch_error(dispatch_error)
                 .help(&HELP),
         )
@@ -1,5 +1,10 @@
+use serenity::client::Context;
+use serenity::framework::standard::CommandResult;
+use serenity::futures::stream::{self, StreamExt};
+use serenity::model::channel::{Message, ReactionType};
 use std::collections::HashMap;
 use std::str::FromStr;
+use std::time::Duration;
 
 pub struct IdNameMap(pub HashMap<u64, String>);
 
@@ -54,3 +59,148 @@ impl IdNameMap {
         }
     }
 }
+
+const PAGE_LEN: usize = 1900;
+const PAGE_TIMEOUT: Duration = Duration::from_secs(120);
+const PREV: &str = "\u{2b05}\u{fe0f}";
+const NEXT: &str = "\u{27a1}\u{fe0f}";
+
+/// Splits lines into code block pages that fit within a discord message.
+/// Lines that are too long to fit on a page by themselves are truncated. All
+/// lengths are in bytes, which are never fewer than discord's characters.
+pub fn pages(header: &str, lines: &[String]) -> Vec<String> {
+    let max = PAGE_LEN - header.len();
+    let mut res = vec![];
+    let mut page = String::new();
+
+    for i in lines {
+        // leave room for the newline
+        let mut end = i.len().min(max - 1);
+        while !i.is_char_boundary(end) {
+            end -= 1;
+        }
+        let line = &i[..end];
+        if !page.is_empty() && page.len() + line.len() + 1 > max {
+            res.push(format!("{}```\n{}```", header, page));
+            page.clear();
+        }
+        page.push_str(line);
+        page.push('\n');
+    }
+    if !page.is_empty() || res.is_empty() {
+        res.push(format!("{}```\n{}```", header, page));
+    }
+
+    res
+}
+
+/// Sends the lines as a code block, splitting them over multiple pages if
+/// needed. The invoker can flip through the pages with reactions or by
+/// sending `!next` and `!prev` for a couple of minutes.
+pub async fn paginate(
+    ctx: &Context,
+    msg: &Message,
+    header: &str,
+    lines: &[String],
+) -> CommandResult {
+    let pages = pages(header, lines);
+    if pages.len() == 1 {
+        msg.channel_id.say(&ctx.http, &pages[0]).await?;
+        return Ok(());
+    }
+
+    let show = |x: usize| format!("{}\npage {}/{}", pages[x], x + 1, pages.len());
+    let mut current = 0;
+    let mut sent = msg.channel_id.say(&ctx.http, show(current)).await?;
+    for i in &[PREV, NEXT] {
+        sent.react(ctx, ReactionType::Unicode(i.to_string()))
+            .await?;
+    }
+
+    // reactions can't be removed without manage messages so removing one
+    // counts as a press as well
+    let reactions = sent
+        .await_reactions(ctx)
+        .author_id(msg.author.id)
+        .added(true)
+        .removed(true)
+        .timeout(PAGE_TIMEOUT)
+        .await
+        .filter_map(|x| async move {
+            match &x.as_inner_ref().emoji {
+                ReactionType::Unicode(x) if x == PREV => Some(false),
+                ReactionType::Unicode(x) if x == NEXT => Some(true),
+                _ => None,
+            }
+        });
+    let replies = msg
+        .channel_id
+        .await_replies(ctx)
+        .author_id(msg.author.id)
+        .timeout(PAGE_TIMEOUT)
+        .await
+        .filter_map(|x| async move {
+            match x.content.trim() {
+                "!prev" => Some(false),
+                "!next" => Some(true),
+                _ => None,
+            }
+        });
+
+    let mut presses = stream::select(reactions, replies).boxed();
+    while let Some(forward) = presses.next().await {
+        let next = match forward {
+            true if current + 1 < pages.len() => current + 1,
+            false if current > 0 => current - 1,
+            _ => continue,
+        };
+        current = next;
+        sent.edit(ctx, |m| m.content(show(current))).await?;
+    }
+
+    Ok(())
+}
+
+#[cfg(test)]
+pub mod test {
+    pub mod pages {
+        use super::super::*;
+
+        #[test]
+        fn single_page() {
+            let lines = vec!["a".to_string(), "b".to_string()];
+            assert_eq!(pages("x ", &lines), vec!["x ```\na\nb\n```"]);
+        }
+
+        #[test]
+        fn empty() {
+            assert_eq!(pages("", &[]), vec!["```\n```"]);
+        }
+
+        #[test]
+        fn splits_long_output() {
+            let lines: Vec<_> = (0..1000).map(|x| format!("line {}", x)).collect();
+            let res = pages("", &lines);
+            assert!(res.len() > 1);
+            assert!(res.iter().all(|x| x.len() <= PAGE_LEN + 7));
+            assert_eq!(res.concat().matches("line").count(), 1000);
+        }
+
+        #[test]
+        fn truncates_long_lines() {
+            let lines = vec!["x".repeat(5000)];
+            let res = pages("", &lines);
+            assert_eq!(res.len(), 1);
+            assert!(res[0].len() <= PAGE_LEN + 7);
+        }
+
+        #[test]
+        fn truncates_multibyte_lines() {
+            let lines = vec!["\u{e9}".repeat(5000), "\u{e9}".repeat(5000)];
+            let res = pages("", &lines);
+            assert_eq!(res.len(), 2);
+            assert!(res.iter().all(|x| x.len() <= PAGE_LEN + 7));
+            assert!(res.iter().all(|x| x != "```\n```"));
+        }
+    }
+}
@@ -0,0 +1,428 @@
+use image::imageops::FilterType;
+use image::{GenericImageView, RgbaImage};
+use rand::seq::SliceRandom;
+use rusqlite::Connection;
+use rusttype::{point, Font, PositionedGlyph, Scale};
+use serenity::client::Context;
+use serenity::framework::standard::macros::{command, group};
+use serenity::framework::standard::Args;
+use serenity::framework::standard::CommandResult;
+use serenity::model::channel::Message;
+use std::error::Error;
+use std::path::{Path, PathBuf};
+
+use crate::modules::memes::EDIT_MEMES_CHECK_CHECK;
+
+/// Templates larger than this are scaled down when added.
+const MAX_SIZE: u32 = 1024;
+const MAX_LINES: usize = 3;
+/// The group's subcommands and their aliases, which would shadow templates
+/// with the same names.
+const RESERVED: &[&str] = &["list", "ls", "add", "del", "rm", "remove", "delete"];
+
+fn template_dir(guild: u64) -> PathBuf {
+    PathBuf::from(crate::config::TEMPLATE_DIR).join(guild.to_string())
+}
+
+fn template_path(guild: u64, name: &str) -> PathBuf {
+    template_dir(guild).join(format!("{}.png", name))
+}
+
+fn templates(guild: u64) -> Vec<String> {
+    let mut res: Vec<_> = std::fs::read_dir(template_dir(guild))
+        .into_iter()
+        .flatten()
+        .filter_map(|x| x.ok())
+        .filter_map(|x| {
+            let path = x.path();
+            match path.extension() {
+                Some(ext) if ext == "png" => Some(path.file_stem()?.to_str()?.to_string()),
+                _ => None,
+            }
+        })
+        .collect();
+    res.sort();
+    res
+}
+
+fn valid_name(name: &str) -> bool {
+    (1..=32).contains(&name.len())
+        && name
+            .chars()
+            .all(|c| c.is_ascii_alphanumeric() || c == '-' || c == '_')
+}
+
+/// Whether a template can be added under `name`. Numbers and subcommand names
+/// would make `!memeimg <name>` do something else.
+fn valid_new_name(name: &str) -> bool {
+    valid_name(name) && name.parse::<i32>().is_err() && !RESERVED.contains(&name)
+}
+
+/// Greedily breaks text into lines no wider than `max` according to
+/// `measure`. Words wider than a line get a line to themselves.
+pub fn wrap<F>(text: &str, max: f32, measure: F) -> Vec<String>
+where
+    F: Fn(&str) -> f32,
+{
+    let mut res = vec![];
+    let mut line = String::new();
+    for word in text.split_whitespace() {
+        let candidate = if line.is_empty() {
+            word.to_string()
+        } else {
+            format!("{} {}", line, word)
+        };
+        if measure(&candidate) <= max || line.is_empty() {
+            line = candidate;
+        } else {
+            res.push(std::mem::replace(&mut line, word.to_string()));
+        }
+    }
+    if !line.is_empty() {
+        res.push(line);
+    }
+    res
+}
+
+fn layout<'a>(
+    font: &'a Font,
+    text: &str,
+    scale: Scale,
+    x: f32,
+    y: f32,
+) -> Vec<PositionedGlyph<'a>> {
+    font.layout(text, scale, point(x, y)).collect()
+}
+
+fn text_width(font: &Font, text: &str, scale: Scale) -> f32 {
+    layout(font, text, scale, 0.0, 0.0)
+        .iter()
+        .filter_map(|x| x.pixel_bounding_box())
+        .map(|x| x.max.x as f32)
+        .fold(0.0, f32::max)
+}
+
+/// Picks the largest font size that fits the text in at most `MAX_LINES`
+/// lines, returning the wrapped lines and the scale.
+fn fit(font: &Font, text: &str, width: f32, height: f32) -> (Vec<String>, Scale) {
+    let mut size = height / 7.0;
+    loop {
+        let scale = Scale::uniform(size);
+        let lines = wrap(&text.to_uppercase(), width, |x| text_width(font, x, scale));
+        let widest = lines
+            .iter()
+            .map(|x| text_width(font, x, scale))
+            .fold(0.0, f32::max);
+        if (lines.len() <= MAX_LINES && widest <= width) || size <= 8.0 {
+            return (lines, scale);
+        }
+        size *= 0.9;
+    }
+}
+
+/// Draws a block of white text with a black outline, either hanging from the
+/// top of the image or sitting on the bottom.
+fn draw_text(img: &mut RgbaImage, font: &Font, text: &str, top: bool) {
+    let (w, h) = img.dimensions();
+    let margin = w as f32 * 0.03;
+    let (lines, scale) = fit(font, text, w as f32 - margin * 2.0, h as f32);
+    let metrics = font.v_metrics(scale);
+    let line_height = metrics.ascent - metrics.descent + metrics.line_gap;
+    let outline = (scale.y / 14.0).max(1.0) as i32;
+
+    let mut y = if top {
+        margin + metrics.ascent
+    } else {
+        h as f32 - margin - line_height * lines.len() as f32 + metrics.ascent
+    };
+
+    // coverage of the text itself, then grown by the outline radius
+    let mut fill = vec![0f32; (w * h) as usize];
+    for line in lines {
+        let x = (w as f32 - text_width(font, &line, scale)) / 2.0;
+        for glyph in layout(font, &line, scale, x, y) {
+            if let Some(bb) = glyph.pixel_bounding_box() {
+                glyph.draw(|gx, gy, v| {
+                    let (px, py) = (gx as i32 + bb.min.x, gy as i32 + bb.min.y);
+                    if px >= 0 && py >= 0 && (px as u32) < w && (py as u32) < h {
+                        let i = (py as u32 * w + px as u32) as usize;
+                        fill[i] = fill[i].max(v);
+                    }
+                });
+            }
+        }
+        y += line_height;
+    }
+
+    let mut edge = vec![0f32; (w * h) as usize];
+    for py in 0..h as i32 {
+        for px in 0..w as i32 {
+            let v = fill[(py as u32 * w + px as u32) as usize];
+            if v == 0.0 {
+                continue;
+            }
+            for dy in -outline..=outline {
+                for dx in -outline..=outline {
+                    let (x, y) = (px + dx, py + dy);
+                    if dx * dx + dy * dy > outline * outline
+                        || x < 0
+                        || y < 0
+                        || x as u32 >= w
+                        || y as u32 >= h
+                    {
+                        continue;
+                    }
+                    let i = (y as u32 * w + x as u32) as usize;
+                    edge[i] = edge[i].max(v);
+                }
+            }
+        }
+    }
+
+    for (i, pixel) in img.pixels_mut().enumerate() {
+        let (e, f) = (edge[i], fill[i]);
+        for c in 0..3 {
+            let v = pixel[c] as f32 * (1.0 - e);
+            pixel[c] = (v * (1.0 - f) + 255.0 * f) as u8;
+        }
+        pixel[3] = pixel[3].max((e * 255.0) as u8);
+    }
+}
+
+/// Renders an image macro and encodes it as a png.
+fn render(
+    template: &Path,
+    top: &str,
+    bottom: &str,
+) -> Result<Vec<u8>, Box<dyn Error + Send + Sync>> {
+    let font =
+        Font::try_from_vec(std::fs::read(crate::config::FONT_PATH)?).ok_or("invalid font")?;
+    let mut img = image::open(template)?.to_rgba8();
+
+    if !top.trim().is_empty() {
+        draw_text(&mut img, &font, top, true);
+    }
+    if !bottom.trim().is_empty() {
+        draw_text(&mut img, &font, bottom, false);
+    }
+
+    let mut res = vec![];
+    image::DynamicImage::ImageRgba8(img).write_to(&mut res, image::ImageOutputFormat::Png)?;
+    Ok(res)
+}
+
+/// Splits text into top and bottom captions on `|`, or in half by words if
+/// there is no separator.
+fn captions(text: &str) -> (String, String) {
+    match text.find('|') {
+        Some(x) => (
+            text[..x].trim().to_string(),
+            text[x + 1..].trim().to_string(),
+        ),
+        None => {
+            let words: Vec<_> = text.split_whitespace().collect();
+            let half = words.len() / 2;
+            (words[..half].join(" "), words[half..].join(" "))
+        }
+    }
+}
+
+#[command]
+#[only_in("guilds")]
+#[usage("<template> <top text> | <bottom text>")]
+/// Renders an image macro. `!memeimg <id>` uses the text of a meme with a
+/// random template instead.
+async fn render_macro(ctx: &Context, msg: &Message, mut args: Args) -> CommandResult {
+    let guild = *msg.guild_id.unwrap().as_u64();
+    let first: String = match args.single() {
+        Ok(x) => x,
+        Err(_) => {
+            msg.channel_id
+                .say(
+                    &ctx.http,
+                    "usage: `!memeimg <template> <top text> | <bottom text>`",
+                )
+                .await?;
+            return Ok(());
+        }
+    };
+
+    let (template, text) = match first.parse::<i32>() {
+        Ok(id) => {
+            let conn = Connection::open(crate::config::DB_PATH)?;
+            let text = crate::modules::memes::text_by_id(&conn, guild, id)?;
+            let template = templates(guild).choose(&mut rand::thread_rng()).cloned();
+            match (text, template) {
+                (Some((_, true)), _) if !crate::modules::memes::nsfw_allowed(ctx, msg).await => {
+                    let res = format!("meme {} is nsfw and can only be used in nsfw channels", id);
+                    msg.channel_id.say(&ctx.http, res).await?;
+                    return Ok(());
+                }
+                (Some((text, _)), Some(template)) => (template, text),
+                (None, _) => {
+                    msg.channel_id
+                        .say(&ctx.http, format!("meme {} not found", id))
+                        .await?;
+                    return Ok(());
+                }
+                (_, None) => {
+                    msg.channel_id
+                        .say(&ctx.http, "no templates have been added yet")
+                        .await?;
+                    return Ok(());
+                }
+            }
+        }
+        Err(_) => (first.to_lowercase(), args.rest().to_string()),
+    };
+
+    let path = template_path(guild, &template);
+    if !valid_name(&template) || !path.exists() {
+        msg.channel_id
+            .say(&ctx.http, format!("no template named `{}`", template))
+            .await?;
+        return Ok(());
+    }
+
+    let (top, bottom) = captions(&text);
+    let png = tokio::task::spawn_blocking(move || render(&path, &top, &bottom))
+        .await?
+        .map_err(|x| x.to_string())?;
+    msg.channel_id
+        .send_files(&ctx.http, vec![(png.as_slice(), "meme.png")], |m| m)
+        .await?;
+    Ok(())
+}
+
+#[command]
+#[aliases(ls)]
+#[only_in("guilds")]
+/// Lists all templates.
+async fn list(ctx: &Context, msg: &Message) -> CommandResult {
+    let names = templates(*msg.guild_id.unwrap().as_u64());
+    let res = if names.is_empty() {
+        "no templates have been added yet".to_string()
+    } else {
+        format!("templates: {}", names.join(", "))
+    };
+    msg.channel_id.say(&ctx.http, res).await?;
+    Ok(())
+}
+
+#[command]
+#[num_args(1)]
+#[only_in("guilds")]
+#[checks(edit_memes_check)]
+#[usage("<name>")]
+/// Adds the attached image as a template, replacing any template with the same
+/// name.
+async fn add(ctx: &Context, msg: &Message, args: Args) -> CommandResult {
+    let guild = *msg.guild_id.unwrap().as_u64();
+    let name = args.rest().trim().to_lowercase();
+
+    let attachment = match msg.attachments.first() {
+        Some(x) => x,
+        None => {
+            msg.channel_id
+                .say(&ctx.http, "attach the image to use as the template")
+                .await?;
+            return Ok(());
+        }
+    };
+    if !valid_new_name(&name) {
+        msg.channel_id
+            .say(
+                &ctx.http,
+                format!(
+                    "template names must be letters, numbers, `-` or `_` and can't be a number \
+                     or one of {}",
+                    RESERVED.join(", ")
+                ),
+            )
+            .await?;
+        return Ok(());
+    }
+
+    let data = attachment.download().await?;
+    let path = template_path(guild, &name);
+    let res = tokio::task::spawn_blocking(move || -> Result<(), Box<dyn Error + Send + Sync>> {
+        let mut img = image::load_from_memory(&data)?;
+        if img.width() > MAX_SIZE || img.height() > MAX_SIZE {
+            img = img.resize(MAX_SIZE, MAX_SIZE, FilterType::Triangle);
+        }
+        std::fs::create_dir_all(path.parent().unwrap())?;
+        img.save(&path)?;
+        Ok(())
+    })
+    .await?;
+
+    let res = match res {
+        Ok(_) => format!("template {} added successfully", name),
+        Err(x) => format!("error adding template: {}", x),
+    };
+    msg.channel_id.say(&ctx.http, res).await?;
+    Ok(())
+}
+
+#[command]
+#[aliases(rm, remove, delete)]
+#[num_args(1)]
+#[only_in("guilds")]
+#[checks(edit_memes_check)]
+#[usage("<name>")]
+/// Removes a template.
+async fn del(ctx: &Context, msg: &Message, args: Args) -> CommandResult {
+    let name = args.rest().trim().to_lowercase();
+    let path = template_path(*msg.guild_id.unwrap().as_u64(), &name);
+
+    let res = if valid_name(&name) && std::fs::remove_file(path).is_ok() {
+        format!("template {} removed successfully", name)
+    } else {
+        format!("no template named `{}`", name)
+    };
+    msg.channel_id.say(&ctx.http, res).await?;
+    Ok(())
+}
+
+#[group]
+#[prefix("memeimg")]
+#[only_in("guilds")]
+#[default_command(render_macro)]
+#[commands(list, add, del)]
+/// The memeimg group renders classic image macros.
+///
+/// `!memeimg <template> <top text> | <bottom text>` - renders an image macro
+/// `!memeimg <id>` - renders a meme on a random template
+/// `!memeimg list` - lists all templates
+///
+/// The following commands require the `m` permission flag.
+///
+/// `!memeimg add <name>` - adds the attached image as a template
+/// `!memeimg del <name>` - removes a template
+pub struct MemeImg;
+
+#[cfg(test)]
+pub mod test {
+    use super::*;
+
+    #[test]
+    fn reserved_names() {
+        assert!(valid_new_name("drake"));
+        assert!(!valid_new_name("ls"));
+        assert!(!valid_new_name("delete"));
+        assert!(!valid_new_name("42"));
+        assert!(valid_name("ls"));
+    }
+
+    #[test]
+    fn wrap_lines() {
+        let res = wrap("aa bb cc dddddd e", 5.0, |x| x.len() as f32);
+        assert_eq!(res, vec!["aa bb", "cc", "dddddd", "e"]);
+    }
+
+    #[test]
+    fn split_captions() {
+        assert_eq!(captions(" top | bottom "), ("top".into(), "bottom".into()));
+        assert_eq!(captions("a b c"), ("a".into(), "b c".into()));
+    }
+}
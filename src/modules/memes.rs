@@ -1,16 +1,348 @@
+use chrono::{TimeZone, Utc};
+use rand::seq::IteratorRandom;
 use rusqlite::Connection;
+use serenity::builder::ParseValue;
 use serenity::client::Context;
 use serenity::framework::standard::macros::{check, command, group};
 use serenity::framework::standard::Args;
 use serenity::framework::standard::CommandResult;
 use serenity::framework::standard::Reason;
-use serenity::model::channel::Message;
+use serenity::model::channel::{Channel, Message};
+use serenity::model::id::{ChannelId, UserId};
+use std::collections::{HashMap, HashSet};
 use std::str::FromStr;
 use std::time::{SystemTime, UNIX_EPOCH};
 
+use crate::misc::{paginate, IdNameMap};
+use crate::modules::settings;
+use markov::MARKOV_COMMAND;
+use pins::IMPORT_PINS_COMMAND;
+use quota::SHOW_QUOTA_COMMAND;
+use stats::SHOW_STATS_COMMAND;
+
+pub mod collection;
+pub mod digest;
+mod dupes;
+pub mod game;
+mod import;
+pub mod inline;
+mod keywords;
+mod markov;
+pub mod onthisday;
+mod pattern;
+pub mod pins;
+pub mod policy;
+pub mod queue;
+mod quota;
+mod renumber;
+pub mod shared;
+mod stats;
+mod template;
+
 pub struct Meme {
     id: i32,
+    time: i64,
     text: String,
+    author: Option<u64>,
+    name: Option<String>,
+    said_by: Option<u64>,
+    locked: bool,
+    nsfw: bool,
+    spoiler: bool,
+    channel: Option<u64>,
+    source: Option<String>,
+    template: bool,
+    tags: Vec<String>,
+}
+
+/// Checks that a meme's text is acceptable, returning the reason it was
+/// rejected otherwise. `policy` is the guild's content policy, if the invoker
+/// is subject to it.
+fn validate(text: &str, policy: Option<&policy::Policy>) -> Result<(), String> {
+    if text.trim().is_empty() {
+        return Err("memes can't be empty".into());
+    }
+
+    match policy {
+        Some(x) => x.check(text),
+        None => Ok(()),
+    }
+}
+
+enum Duplicate {
+    Exact(i32),
+    Near(i32),
+    None,
+}
+
+/// Looks for an existing meme that is the same as or very similar to `text`.
+fn find_duplicate(conn: &Connection, table: &str, text: &str) -> rusqlite::Result<Duplicate> {
+    let print = dupes::Fingerprint::new(text);
+    let mut res = Duplicate::None;
+    for i in sql::all_memes(conn, table)? {
+        let other = dupes::Fingerprint::new(&i.text);
+        if print.is_exact(&other) {
+            return Ok(Duplicate::Exact(i.id));
+        } else if let Duplicate::None = res {
+            if print.is_near(&other) {
+                res = Duplicate::Near(i.id);
+            }
+        }
+    }
+
+    Ok(res)
+}
+
+/// Words `!meme` treats specially before looking up names.
+const RESERVED: &[&str] = &["global", "onthisday"];
+
+/// Meme names are short single words that can't be confused with an id or a
+/// `!meme` keyword.
+fn valid_name(name: &str) -> bool {
+    (1..=32).contains(&name.chars().count())
+        && name
+            .chars()
+            .all(|c| c.is_alphanumeric() || c == '-' || c == '_')
+        && !name.chars().all(|c| c.is_ascii_digit())
+        && !RESERVED.contains(&name)
+}
+
+/// Shortens a meme to a single line for use in listings.
+fn preview(text: &str) -> String {
+    let line = text.split_whitespace().collect::<Vec<_>>().join(" ");
+    match line.char_indices().nth(80) {
+        Some((x, _)) => format!("{}...", &line[..x]),
+        None => line,
+    }
+}
+
+/// Whether the invoker may change a meme. Members with the `m` flag always
+/// can, otherwise only the member who added it or said it can, and only while
+/// it isn't locked.
+async fn can_edit(ctx: &Context, msg: &Message, meme: &Meme) -> bool {
+    if crate::modules::perms::check_perms(ctx, msg, "m")
+        .await
+        .is_ok()
+    {
+        return true;
+    }
+    let user = *msg.author.id.as_u64();
+    !meme.locked && (meme.author == Some(user) || meme.said_by == Some(user))
+}
+
+/// The pools `!meme` draws random and search results from, in order. Channels
+/// with memes of their own use those first, falling back to the guild's memes
+/// only if the `channel_fallback` setting is on.
+fn pools(
+    conn: &Connection,
+    table: &str,
+    guild: u64,
+    channel: u64,
+) -> rusqlite::Result<Vec<Option<u64>>> {
+    if sql::pool_size(conn, table, Some(channel))? == 0 {
+        Ok(vec![None])
+    } else if settings::get_bool(conn, guild, "channel_fallback")? {
+        Ok(vec![Some(channel), None])
+    } else {
+        Ok(vec![Some(channel)])
+    }
+}
+
+fn now() -> i64 {
+    SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .unwrap()
+        .as_secs() as i64
+}
+
+/// Gets the text of a meme and whether it's NSFW for use by other modules.
+pub fn text_by_id(
+    conn: &Connection,
+    guild: u64,
+    id: i32,
+) -> rusqlite::Result<Option<(String, bool)>> {
+    let table = sql::table(conn, guild)?;
+    match sql::meme_by_id(conn, &table, id) {
+        Ok(x) => Ok(Some((x.text, x.nsfw))),
+        Err(rusqlite::Error::QueryReturnedNoRows) => Ok(None),
+        Err(x) => Err(x),
+    }
+}
+
+/// Fills in a meme's placeholders for the message that requested it. Only
+/// memes flagged as templates have placeholders, everything else is sent as
+/// it was written.
+async fn render(ctx: &Context, msg: &Message, meme: &Meme, args: &[String]) -> String {
+    if !meme.template {
+        return spoiler(meme, meme.text.clone());
+    }
+
+    let mut values = HashMap::new();
+    for i in template::fields(&meme.text) {
+        if values.contains_key(&i) {
+            continue;
+        }
+        let value = match i.as_str() {
+            "user" => Some(
+                msg.author_nick(ctx)
+                    .await
+                    .unwrap_or_else(|| msg.author.name.clone()),
+            ),
+            "author" => match meme.author {
+                Some(x) => UserId(x).to_user(ctx).await.ok().map(|x| x.name),
+                None => None,
+            },
+            "random_member" => msg
+                .guild_field(ctx, |g| {
+                    g.members
+                        .values()
+                        .choose(&mut rand::thread_rng())
+                        .map(|x| x.display_name().into_owned())
+                })
+                .await
+                .flatten(),
+            "channel" => Some(format!("<#{}>", msg.channel_id)),
+            "date" => Some(Utc::now().format("%Y-%m-%d").to_string()),
+            _ => None,
+        };
+        if let Some(x) = value {
+            values.insert(i, x);
+        }
+    }
+
+    let text = template::expand(&meme.text, args, |x| values.get(x).cloned());
+    spoiler(meme, text)
+}
+
+fn spoiler(meme: &Meme, text: String) -> String {
+    if meme.spoiler {
+        // a `||` in the meme would end the spoiler early
+        format!("||{}||", text.replace('|', "\\|"))
+    } else {
+        text
+    }
+}
+
+/// Whether NSFW memes may be sent to a channel.
+pub async fn channel_nsfw(ctx: &Context, channel: ChannelId) -> bool {
+    match channel.to_channel_cached(ctx).await {
+        Some(Channel::Guild(x)) => x.is_nsfw(),
+        _ => false,
+    }
+}
+
+/// Whether NSFW memes may be sent to the channel the message was sent in.
+pub async fn nsfw_allowed(ctx: &Context, msg: &Message) -> bool {
+    channel_nsfw(ctx, msg.channel_id).await
+}
+
+/// Shortens a meme for use in listings, hiding the text of NSFW memes unless
+/// they're allowed where the listing is sent.
+fn listing(meme: &Meme, nsfw: bool) -> String {
+    if meme.nsfw && !nsfw {
+        "(nsfw)".to_string()
+    } else {
+        preview(&meme.text)
+    }
+}
+
+/// Sends a meme to the channel the command was invoked in, either as plain
+/// text or as an embed depending on the guild's `meme_embed` setting, and
+/// records the fetch for `!memes stats`. NSFW memes are refused outside of
+/// NSFW channels. The meme is sent as a reply to `reply` if it's given.
+async fn send_meme(
+    ctx: &Context,
+    msg: &Message,
+    meme: &Meme,
+    args: &[String],
+    embed: bool,
+    reply: Option<&Message>,
+) -> CommandResult {
+    if meme.nsfw && !nsfw_allowed(ctx, msg).await {
+        let res = format!(
+            "meme {} is nsfw and can only be sent in nsfw channels",
+            meme.id
+        );
+        msg.channel_id.say(&ctx.http, res).await?;
+        return Ok(());
+    }
+
+    let text = render(ctx, msg, meme, args).await;
+    if embed {
+        send_embed(ctx, msg, meme, &text, None, reply).await?;
+    } else {
+        msg.channel_id
+            .send_message(&ctx.http, |m| {
+                if let Some(x) = reply {
+                    m.reference_message(x);
+                }
+                // arguments and member names could otherwise ping everyone
+                if meme.template {
+                    m.allowed_mentions(|x| x.parse(ParseValue::Users));
+                }
+                m.content(&text)
+            })
+            .await?;
+    }
+
+    let conn = Connection::open(crate::config::DB_PATH)?;
+    stats::record(
+        &conn,
+        *msg.guild_id.unwrap().as_u64(),
+        meme.id,
+        *msg.channel_id.as_u64(),
+        *msg.author.id.as_u64(),
+        now(),
+    )?;
+    Ok(())
+}
+
+/// Sends a meme as an embed. `origin` is the name of the guild the meme came
+/// from if it isn't the current one.
+async fn send_embed(
+    ctx: &Context,
+    msg: &Message,
+    meme: &Meme,
+    text: &str,
+    origin: Option<&str>,
+    reply: Option<&Message>,
+) -> CommandResult {
+    let mut footer = format!("#{}", meme.id);
+    if let Some(x) = &meme.name {
+        footer.push_str(&format!(" ({})", x));
+    }
+    if let Some(x) = origin {
+        footer.push_str(&format!(" from {}", x));
+    }
+    if let Some(x) = meme.author {
+        if let Ok(user) = UserId(x).to_user(ctx).await {
+            footer.push_str(&format!(" \u{2022} added by {}", user.tag()));
+        }
+    }
+    if !meme.tags.is_empty() {
+        footer.push_str(&format!(" \u{2022} tags: {}", meme.tags.join(", ")));
+    }
+    let time = Utc.timestamp_opt(meme.time, 0).single();
+
+    msg.channel_id
+        .send_message(&ctx.http, |m| {
+            if let Some(x) = reply {
+                m.reference_message(x);
+            }
+            m.embed(|e| {
+                match &meme.source {
+                    Some(x) => e.description(format!("{}\n\n[source]({})", text, x)),
+                    None => e.description(text),
+                };
+                e.footer(|f| f.text(&footer));
+                if let Some(x) = time.filter(|_| meme.time != 0) {
+                    e.timestamp(&x);
+                }
+                e
+            })
+        })
+        .await?;
+    Ok(())
 }
 
 // TODO: replace this disgusting string splitting with access to the args object
@@ -22,94 +354,834 @@ pub struct Meme {
 /// Usage examples:
 /// # Getting a random meme:
 /// `!meme`
+/// # Getting a meme to go with a message, using its keywords:
+/// `!meme` as a reply to the message
 /// # Getting a random meme matching a search:
 /// `!meme <search string>`
+/// # Getting a random meme matching a regular expression:
+/// `!meme /<pattern>/`
 /// # Getting the latest meme:
 /// `!meme 0`
 /// # Getting a meme matching an id:
 /// `!meme <id number>`
+/// # Getting a meme by its name, filling in its placeholders if it's a template:
+/// `!meme <name> [args...]`
+/// # Listing memes added on today's date in previous years:
+/// `!meme onthisday`
+/// # Getting a meme shared by another guild:
+/// `!meme global` or `!meme @<collection>`
+///
+/// Random memes and searches come from the channel's own memes if it has any.
 async fn meme(ctx: &Context, msg: &Message, args: Args) -> CommandResult {
     let conn = Connection::open(crate::config::DB_PATH)?;
-    let table = sql::table(&conn, *msg.guild_id.unwrap().as_u64())?;
+    let guild = *msg.guild_id.unwrap().as_u64();
+    let table = sql::table(&conn, guild)?;
+    let embed = settings::get_bool(&conn, guild, "meme_embed")?;
+    let nsfw = nsfw_allowed(ctx, msg).await;
+    let pools = pools(&conn, &table, guild, *msg.channel_id.as_u64())?;
     let arg = args.rest().to_string();
 
-    let text = if arg.is_empty() {
-        sql::random_meme(&conn, &table)?.text
+    let mut words = args.clone();
+    let name = words.single_quoted::<String>().unwrap_or_default();
+    let values: Vec<String> = words.quoted().iter().filter_map(|x| x.ok()).collect();
+
+    let reply = if arg.is_empty() {
+        msg.referenced_message.as_deref()
+    } else {
+        None
+    };
+
+    let res = if arg.is_empty() {
+        let words = reply.map(|x| keywords::keywords(&x.content));
+        let memes = match &words {
+            Some(x) if !x.is_empty() => sql::all_memes(&conn, &table)?,
+            _ => vec![],
+        };
+        let best = pools.iter().find_map(|x| {
+            let pool: Vec<_> = memes
+                .iter()
+                .filter(|y| y.channel == *x && (nsfw || !y.nsfw))
+                .collect();
+            keywords::best(&pool, words.as_deref().unwrap_or_default()).map(|y| y.id)
+        });
+        match best {
+            Some(x) => {
+                sql::meme_by_id(&conn, &table, x).map_err(|_| format!("meme {} not found", x))
+            }
+            None => pools
+                .iter()
+                .find_map(|x| sql::random_meme(&conn, &table, *x, nsfw).ok())
+                .ok_or_else(|| "no memes found".to_string()),
+        }
+    } else if arg == "onthisday" {
+        return onthisday::reply(ctx, msg).await;
+    } else if arg == "global" || arg.starts_with('@') {
+        return shared::reply(ctx, msg, &arg).await;
+    } else if let Some(x) = pattern::parse(&arg) {
+        let regex = match pattern::compile(x) {
+            Ok(y) => y,
+            Err(y) => {
+                msg.channel_id.say(&ctx.http, y).await?;
+                return Ok(());
+            }
+        };
+        let mut memes = sql::all_memes(&conn, &table)?;
+        let mut res = Err(format!("meme matching {} not found", arg));
+        for i in pools.iter() {
+            let (pool, rest): (Vec<_>, Vec<_>) = memes
+                .into_iter()
+                .partition(|y| y.channel == *i && (nsfw || !y.nsfw));
+            memes = rest;
+            match pattern::search(pool, regex.clone()).await {
+                Ok(Some(y)) => {
+                    res = Ok(y);
+                    break;
+                }
+                Ok(None) => (),
+                Err(y) => {
+                    res = Err(y);
+                    break;
+                }
+            }
+        }
+        res
+    } else if let Some(x) = sql::meme_by_name(&conn, &table, &name.to_lowercase())
+        .ok()
+        .filter(|x| values.is_empty() || x.template)
+    {
+        // anything after a name is only taken as arguments for templates, so
+        // searches that happen to start with a name still work
+        send_meme(ctx, msg, &x, &values, embed, None).await?;
+        return Ok(());
     } else {
         match i32::from_str(&arg) {
-            Ok(x) => match if x != 0 {
-                sql::meme_by_id(&conn, &table, x)
-            } else {
-                sql::latest_meme(&conn, &table)
-            } {
-                Ok(y) => y.text,
-                Err(_) => format!("meme {} not found", x),
-            },
-            Err(_) => match sql::search_meme(&conn, &table, &arg) {
-                Ok(y) => y.text,
-                Err(_) => format!("meme matching \"{}\" not found", arg),
+            Ok(0) => sql::latest_meme(&conn, &table).map_err(|_| "meme 0 not found".to_string()),
+            Ok(x) => match (
+                sql::meme_by_id(&conn, &table, x),
+                sql::renumbered(&conn, guild, x)?,
+            ) {
+                (Ok(y), Some(z)) => {
+                    let res = format!(
+                        "note: meme ids have been renumbered, the meme that used to be {} is now {}",
+                        x, z
+                    );
+                    msg.channel_id.say(&ctx.http, res).await?;
+                    Ok(y)
+                }
+                (Ok(y), None) => Ok(y),
+                (Err(_), Some(z)) => {
+                    let res = format!("meme {} has been renumbered to {}", x, z);
+                    msg.channel_id.say(&ctx.http, res).await?;
+                    sql::meme_by_id(&conn, &table, z).map_err(|_| format!("meme {} not found", z))
+                }
+                (Err(_), None) => Err(format!("meme {} not found", x)),
             },
+            Err(_) => pools
+                .iter()
+                .find_map(|x| sql::search_meme(&conn, &table, &arg, *x, nsfw).ok())
+                .ok_or_else(|| format!("meme matching \"{}\" not found", arg)),
         }
     };
 
-    msg.channel_id.say(&ctx.http, text).await?;
+    match res {
+        Ok(x) => send_meme(ctx, msg, &x, &[], embed, reply).await?,
+        Err(x) => drop(msg.channel_id.say(&ctx.http, x).await?),
+    }
     Ok(())
 }
 
 #[command]
 #[only_in("guilds")]
 #[checks(edit_memes_check)]
-#[usage("<text>")]
-/// Adds a meme to the list.
+#[usage("[--here] <text>")]
+/// Adds a meme to the list. With `--here` the meme is added to the current
+/// channel's own memes instead of the guild's.
 async fn addmeme(ctx: &Context, msg: &Message, args: Args) -> CommandResult {
+    let conn = Connection::open(crate::config::DB_PATH)?;
+    let guild = *msg.guild_id.unwrap().as_u64();
+    let table = sql::table(&conn, guild)?;
+    let here = args.rest().split_whitespace().next() == Some("--here");
+    let arg = if here {
+        args.rest().trim_start()["--here".len()..]
+            .trim_start()
+            .to_string()
+    } else {
+        args.rest().to_string()
+    };
+    let channel = if here {
+        Some(*msg.channel_id.as_u64())
+    } else {
+        None
+    };
+    if let Some(x) = quota::exceeded(ctx, msg, quota::Action::Add).await? {
+        msg.channel_id.say(&ctx.http, x).await?;
+        return Ok(());
+    }
+    let policy = policy::for_invoker(ctx, msg).await?;
+
+    if let Err(x) = validate(&arg, policy.as_ref()) {
+        msg.channel_id.say(&ctx.http, x).await?;
+        return Ok(());
+    }
+
+    let similar = match find_duplicate(&conn, &table, &arg)? {
+        Duplicate::Exact(x) => {
+            let res = format!("that meme already exists as meme {}", x);
+            msg.channel_id.say(&ctx.http, res).await?;
+            return Ok(());
+        }
+        Duplicate::Near(x) => Some(x),
+        Duplicate::None => None,
+    };
+
+    sql::add_meme(&conn, &table, now(), &arg, *msg.author.id.as_u64(), channel)?;
+    let id = sql::get_seq(&conn, &table)?;
+    quota::record(&conn, guild, *msg.author.id.as_u64(), quota::Action::Add)?;
+    markov::added(ctx, guild, &arg).await;
+    let res = match similar {
+        Some(x) => format!(
+            "meme {} added successfully (warning: it looks a lot like meme {})",
+            id, x
+        ),
+        None => format!("meme {} added successfully", id),
+    };
+    msg.channel_id.say(&ctx.http, &res).await?;
+    Ok(())
+}
+
+#[command]
+#[min_args(2)]
+#[only_in("guilds")]
+#[checks(edit_memes_check)]
+#[usage("<id> <tags...>")]
+/// Tags a meme, the tags are shown when memes are sent as embeds. Giving a tag
+/// the meme already has removes it.
+async fn tagmeme(ctx: &Context, msg: &Message, mut args: Args) -> CommandResult {
+    let conn = Connection::open(crate::config::DB_PATH)?;
+    let table = sql::table(&conn, *msg.guild_id.unwrap().as_u64())?;
+    let id: i32 = args.single()?;
+
+    let res = match sql::meme_by_id(&conn, &table, id) {
+        Ok(x) => {
+            let mut tags = x.tags;
+            for i in args.iter::<String>().filter_map(|x| x.ok()) {
+                let tag = i.to_lowercase();
+                if !valid_name(&tag) {
+                    msg.channel_id
+                        .say(
+                            &ctx.http,
+                            "tags must be a single word of letters, numbers, `-` or `_` and \
+                             can't be a number, `global` or `onthisday`",
+                        )
+                        .await?;
+                    return Ok(());
+                }
+                match tags.iter().position(|x| *x == tag) {
+                    Some(x) => {
+                        tags.remove(x);
+                    }
+                    None => tags.push(tag),
+                }
+            }
+            sql::set_tags(&conn, &table, id, &tags)?;
+            if tags.is_empty() {
+                format!("meme {} no longer has any tags", id)
+            } else {
+                format!("meme {} is now tagged {}", id, tags.join(", "))
+            }
+        }
+        Err(_) => format!("meme {} not found", id),
+    };
+
+    msg.channel_id.say(&ctx.http, res).await?;
+    Ok(())
+}
+
+#[command]
+#[min_args(1)]
+#[only_in("guilds")]
+#[checks(edit_memes_check)]
+#[usage("<id> [name]")]
+/// Gives a meme a unique name so it can be fetched with `!meme <name>`. Leaving
+/// out the name removes the meme's current name.
+async fn namememe(ctx: &Context, msg: &Message, mut args: Args) -> CommandResult {
+    let conn = Connection::open(crate::config::DB_PATH)?;
+    let table = sql::table(&conn, *msg.guild_id.unwrap().as_u64())?;
+    let id: i32 = args.single()?;
+    let name = args.rest().trim().to_lowercase();
+
+    let res = if sql::meme_by_id(&conn, &table, id).is_err() {
+        format!("meme {} not found", id)
+    } else if name.is_empty() {
+        sql::set_name(&conn, &table, id, None)?;
+        format!("meme {} no longer has a name", id)
+    } else if !valid_name(&name) {
+        "names must be a single word of letters, numbers, `-` or `_` and can't be a number, \
+         `global` or `onthisday`"
+            .to_string()
+    } else {
+        match sql::meme_by_name(&conn, &table, &name) {
+            Ok(x) if x.id != id => format!("meme {} is already named {}", x.id, name),
+            _ => {
+                sql::set_name(&conn, &table, id, Some(&name))?;
+                format!("meme {} is now named {}", id, name)
+            }
+        }
+    };
+
+    msg.channel_id.say(&ctx.http, res).await?;
+    Ok(())
+}
+
+#[command]
+#[min_args(1)]
+#[only_in("guilds")]
+#[checks(edit_memes_check)]
+#[usage("<id> [member]")]
+/// Tags a meme with the member who originally said it, for use by `!memegame`.
+/// Leaving out the member removes the tag.
+async fn saidby(ctx: &Context, msg: &Message, mut args: Args) -> CommandResult {
     let conn = Connection::open(crate::config::DB_PATH)?;
     let table = sql::table(&conn, *msg.guild_id.unwrap().as_u64())?;
+    let id: i32 = args.single()?;
+    let query = args.rest().trim().to_string();
+
+    let res = if sql::meme_by_id(&conn, &table, id).is_err() {
+        format!("meme {} not found", id)
+    } else if query.is_empty() {
+        sql::set_said_by(&conn, &table, id, None)?;
+        format!("meme {} is no longer tagged", id)
+    } else if let Some(x) = serenity::utils::parse_username(&query) {
+        sql::set_said_by(&conn, &table, id, Some(x))?;
+        match UserId(x).to_user(ctx).await {
+            Ok(user) => format!("meme {} was said by {}", id, user.tag()),
+            Err(_) => format!("meme {} was said by {}", id, x),
+        }
+    } else {
+        let mut map = IdNameMap::new();
+        map.0.extend(
+            msg.guild_field(ctx, |g| {
+                g.members
+                    .iter()
+                    .map(|(k, v)| (*k.as_u64(), v.user.tag()))
+                    .collect::<Vec<_>>()
+            })
+            .await
+            .unwrap_or_default(),
+        );
+        map.lookup(&query, |x, name| {
+            match sql::set_said_by(&conn, &table, id, Some(x)) {
+                Ok(_) => format!("meme {} was said by {}", id, name),
+                Err(_) => "error tagging meme".to_string(),
+            }
+        })
+    };
+
+    msg.channel_id.say(&ctx.http, res).await?;
+    Ok(())
+}
+
+#[command]
+#[only_in("guilds")]
+#[usage("<text>")]
+/// Submits a meme for review. It will be added to the list once someone with
+/// the `m` permission flag approves it.
+async fn submitmeme(ctx: &Context, msg: &Message, args: Args) -> CommandResult {
+    let conn = Connection::open(crate::config::DB_PATH)?;
+    let guild = *msg.guild_id.unwrap().as_u64();
+    let table = sql::table(&conn, guild)?;
+    let queue = queue::sql::table(&conn, guild)?;
     let arg = args.rest().to_string();
+    if let Some(x) = quota::exceeded(ctx, msg, quota::Action::Submit).await? {
+        msg.channel_id.say(&ctx.http, x).await?;
+        return Ok(());
+    }
+    let policy = policy::for_invoker(ctx, msg).await?;
 
-    let time = SystemTime::now()
-        .duration_since(UNIX_EPOCH)
-        .unwrap()
-        .as_secs();
+    if let Err(x) = validate(&arg, policy.as_ref()) {
+        msg.channel_id.say(&ctx.http, x).await?;
+        return Ok(());
+    }
 
-    sql::add_meme(&conn, &table, time as i64, &arg)?;
-    let id = sql::get_seq(&conn, &table)?;
+    if let Duplicate::Exact(x) = find_duplicate(&conn, &table, &arg)? {
+        let res = format!("that meme already exists as meme {}", x);
+        msg.channel_id.say(&ctx.http, res).await?;
+        return Ok(());
+    }
+
+    let id = queue::sql::add_submission(
+        &conn,
+        &queue,
+        &queue::Submission {
+            id: 0,
+            time: now(),
+            text: arg,
+            author: *msg.author.id.as_u64(),
+            channel: *msg.channel_id.as_u64(),
+            message: *msg.id.as_u64(),
+        },
+    )?;
+    quota::record(&conn, guild, *msg.author.id.as_u64(), quota::Action::Submit)?;
     msg.channel_id
-        .say(&ctx.http, &format!("meme {} added successfully", id))
+        .say(
+            &ctx.http,
+            format!("meme submitted for review as submission {}", id),
+        )
         .await?;
     Ok(())
 }
 
+#[command]
+#[min_args(2)]
+#[only_in("guilds")]
+#[usage("<id> <text>")]
+/// Replaces the text of a meme. Members can edit memes they added or said
+/// unless a curator has locked them; editing any other meme requires the `m`
+/// permission flag.
+async fn editmeme(ctx: &Context, msg: &Message, mut args: Args) -> CommandResult {
+    let conn = Connection::open(crate::config::DB_PATH)?;
+    let guild = *msg.guild_id.unwrap().as_u64();
+    let table = sql::table(&conn, guild)?;
+    let id: i32 = args.single()?;
+    let text = args.rest().to_string();
+    let policy = policy::for_invoker(ctx, msg).await?;
+
+    let meme = match sql::meme_by_id(&conn, &table, id) {
+        Ok(x) => x,
+        Err(_) => {
+            msg.channel_id
+                .say(&ctx.http, format!("meme {} not found", id))
+                .await?;
+            return Ok(());
+        }
+    };
+
+    let res = if !can_edit(ctx, msg, &meme).await {
+        format!("you can't edit meme {}", id)
+    } else if let Err(x) = validate(&text, policy.as_ref()) {
+        x
+    } else {
+        match find_duplicate(&conn, &table, &text)? {
+            Duplicate::Exact(x) if x != id => format!("that's already meme {}", x),
+            _ => {
+                sql::set_text(&conn, &table, id, &text)?;
+                if !meme.nsfw {
+                    markov::removed(ctx, guild, &meme.text).await;
+                    markov::added(ctx, guild, &text).await;
+                }
+                format!("meme {} edited", id)
+            }
+        }
+    };
+
+    msg.channel_id.say(&ctx.http, res).await?;
+    Ok(())
+}
+
 #[command]
 #[only_in("guilds")]
-#[checks(edit_memes_check)]
 #[usage("<id>")]
-/// Removes a meme from the list.
+/// Removes a meme from the list. Members can remove memes they added or said
+/// unless a curator has locked them; removing any other meme requires the `m`
+/// permission flag.
 async fn delmeme(ctx: &Context, msg: &Message, args: Args) -> CommandResult {
     let mut conn = Connection::open(crate::config::DB_PATH)?;
-    let table = sql::table(&conn, *msg.guild_id.unwrap().as_u64())?;
+    let guild = *msg.guild_id.unwrap().as_u64();
+    let table = sql::table(&conn, guild)?;
     let arg = i32::from_str(args.rest())?;
 
+    if let Ok(x) = sql::meme_by_id(&conn, &table, arg) {
+        if !can_edit(ctx, msg, &x).await {
+            msg.channel_id
+                .say(&ctx.http, format!("you can't delete meme {}", arg))
+                .await?;
+            return Ok(());
+        }
+    }
+
     // this needs to be in its own scope as tx is not compatable with .await
-    let res = {
+    let deleted = {
         let tx = conn.transaction()?;
         let res = match sql::meme_by_id(&tx, &table, arg) {
             Ok(x) => {
                 sql::del_meme(&tx, &table, arg)?;
+                // ids get reused, so the meme has to stop being shared and
+                // leave any collections now
+                shared::sql::del_meme_entries(&tx, guild, arg)?;
+                collection::sql::del_meme_entries(&tx, guild, arg)?;
                 match sql::latest_meme(&tx, &table) {
                     Ok(x) => sql::set_seq(&tx, &table, x.id),
                     Err(_) => sql::set_seq(&tx, &table, 0),
                 }?;
-                format!("successfully deleted meme {}: {}", arg, x.text)
+                Some(x)
             }
-            Err(_) => "error deleting meme (it probably doesn't exist to begin with)".into(),
+            Err(_) => None,
         };
         tx.commit()?;
         res
     };
+
+    let res = match deleted {
+        Some(x) => {
+            if !x.nsfw {
+                markov::removed(ctx, guild, &x.text).await;
+            }
+            format!("successfully deleted meme {}: {}", arg, x.text)
+        }
+        None => "error deleting meme (it probably doesn't exist to begin with)".into(),
+    };
+    msg.channel_id.say(&ctx.http, res).await?;
+    Ok(())
+}
+
+#[command]
+#[only_in("guilds")]
+#[checks(edit_memes_check)]
+#[usage("<id>")]
+/// Locks a meme so that the member who added or said it can no longer edit or
+/// delete it. Locking a locked meme unlocks it.
+async fn lockmeme(ctx: &Context, msg: &Message, args: Args) -> CommandResult {
+    let conn = Connection::open(crate::config::DB_PATH)?;
+    let table = sql::table(&conn, *msg.guild_id.unwrap().as_u64())?;
+    let id = i32::from_str(args.rest().trim())?;
+
+    let res = match sql::meme_by_id(&conn, &table, id) {
+        Ok(x) => {
+            sql::set_locked(&conn, &table, id, !x.locked)?;
+            if x.locked {
+                format!("meme {} unlocked", id)
+            } else {
+                format!("meme {} locked", id)
+            }
+        }
+        Err(_) => format!("meme {} not found", id),
+    };
+
+    msg.channel_id.say(&ctx.http, res).await?;
+    Ok(())
+}
+
+#[command]
+#[num_args(2)]
+#[only_in("guilds")]
+#[checks(edit_memes_check)]
+#[usage("<id> <here|guild>")]
+/// Moves a meme into the current channel's own memes, or back to the guild's.
+async fn scopememe(ctx: &Context, msg: &Message, mut args: Args) -> CommandResult {
+    let conn = Connection::open(crate::config::DB_PATH)?;
+    let table = sql::table(&conn, *msg.guild_id.unwrap().as_u64())?;
+    let id: i32 = args.single()?;
+    let scope = args.single::<String>()?.to_lowercase();
+
+    let channel = match scope.as_str() {
+        "here" => Some(Some(*msg.channel_id.as_u64())),
+        "guild" => Some(None),
+        _ => None,
+    };
+
+    let res = match (sql::meme_by_id(&conn, &table, id), channel) {
+        (Err(_), _) => format!("meme {} not found", id),
+        (_, None) => "scopes are `here` and `guild`".to_string(),
+        (Ok(x), Some(channel)) if x.channel == channel => {
+            format!("meme {} is already scoped to {}", id, scope)
+        }
+        (Ok(_), Some(channel)) => {
+            sql::set_channel(&conn, &table, id, channel)?;
+            format!("meme {} is now scoped to {}", id, scope)
+        }
+    };
+
+    msg.channel_id.say(&ctx.http, res).await?;
+    Ok(())
+}
+
+#[command]
+#[num_args(2)]
+#[only_in("guilds")]
+#[checks(edit_memes_check)]
+#[usage("<id> <nsfw|spoiler|template>")]
+/// Flags a meme as NSFW, as a spoiler or as a template. NSFW memes are only
+/// sent in NSFW channels, spoilers are hidden behind spoiler tags and templates
+/// have their placeholders filled in, see `!memes preview`. Flagging a meme
+/// with a flag it already has removes the flag.
+async fn flagmeme(ctx: &Context, msg: &Message, mut args: Args) -> CommandResult {
+    let conn = Connection::open(crate::config::DB_PATH)?;
+    let guild = *msg.guild_id.unwrap().as_u64();
+    let table = sql::table(&conn, guild)?;
+    let id: i32 = args.single()?;
+    let flag = args.single::<String>()?.to_lowercase();
+
+    let res = match sql::meme_by_id(&conn, &table, id) {
+        Ok(x) => {
+            let value = match flag.as_str() {
+                "nsfw" => Some(!x.nsfw),
+                "spoiler" => Some(!x.spoiler),
+                "template" => Some(!x.template),
+                _ => None,
+            };
+            match value {
+                Some(value) => {
+                    sql::set_flag(&conn, &table, id, &flag, value)?;
+                    match (flag.as_str(), value) {
+                        ("nsfw", true) => markov::removed(ctx, guild, &x.text).await,
+                        ("nsfw", false) => markov::added(ctx, guild, &x.text).await,
+                        _ => (),
+                    }
+                    if value {
+                        format!("meme {} flagged as {}", id, flag)
+                    } else {
+                        format!("meme {} is no longer flagged as {}", id, flag)
+                    }
+                }
+                None => "flags are `nsfw`, `spoiler` and `template`".to_string(),
+            }
+        }
+        Err(_) => format!("meme {} not found", id),
+    };
+
+    msg.channel_id.say(&ctx.http, res).await?;
+    Ok(())
+}
+
+#[command]
+#[only_in("guilds")]
+#[checks(edit_memes_check)]
+/// Adds every meme in the attached file to the list. The file may be plain
+/// text with one meme per line, a `.csv` file with a `text` column or a
+/// `.json` array of strings or objects with a `text` field.
+async fn import(ctx: &Context, msg: &Message) -> CommandResult {
+    let attachment = match msg.attachments.first() {
+        Some(x) => x,
+        None => {
+            msg.channel_id
+                .say(&ctx.http, "attach a file containing the memes to import")
+                .await?;
+            return Ok(());
+        }
+    };
+    let data = attachment.download().await?;
+    let entries = match import::parse(&attachment.filename, &data) {
+        Ok(x) => x,
+        Err(x) => {
+            msg.channel_id.say(&ctx.http, x).await?;
+            return Ok(());
+        }
+    };
+
+    let mut conn = Connection::open(crate::config::DB_PATH)?;
+    let guild = *msg.guild_id.unwrap().as_u64();
+    let table = sql::table(&conn, guild)?;
+    let author = *msg.author.id.as_u64();
+    let time = now();
+    let policy = policy::for_invoker(ctx, msg).await?;
+    let mut allowance = quota::allowance(ctx, msg, quota::Action::Add).await?;
+
+    // this needs to be in its own scope as tx is not compatable with .await
+    let (added, skipped, rejected, duplicates, over) = {
+        let tx = conn.transaction()?;
+        let mut seen: HashSet<String> = sql::all_memes(&tx, &table)?
+            .into_iter()
+            .map(|x| dupes::normalize(&x.text))
+            .collect();
+        let (mut added, mut skipped, mut rejected, mut duplicates, mut over) = (vec![], 0, 0, 0, 0);
+
+        for i in entries {
+            if validate(&i.text, None).is_err()
+                || matches!(i.time, Some(x) if !import::valid_time(x))
+            {
+                skipped += 1;
+            } else if validate(&i.text, policy.as_ref()).is_err() {
+                rejected += 1;
+            } else if !seen.insert(dupes::normalize(&i.text)) {
+                duplicates += 1;
+            } else if allowance == Some(0) {
+                over += 1;
+            } else {
+                sql::add_meme(&tx, &table, i.time.unwrap_or(time), &i.text, author, None)?;
+                quota::record(&tx, guild, author, quota::Action::Add)?;
+                allowance = allowance.map(|x| x - 1);
+                added.push(i.text);
+            }
+        }
+        tx.commit()?;
+        (added, skipped, rejected, duplicates, over)
+    };
+
+    for i in added.iter() {
+        markov::added(ctx, guild, i).await;
+    }
+
+    let mut res = format!(
+        "imported {} memes ({} invalid entries skipped, {} rejected by the content policy, {} \
+         duplicates skipped)",
+        added.len(),
+        skipped,
+        rejected,
+        duplicates
+    );
+    if over > 0 {
+        res.push_str(&format!(
+            "\n{} memes weren't added as you've reached your addmeme quota",
+            over
+        ));
+    }
     msg.channel_id.say(&ctx.http, res).await?;
     Ok(())
 }
 
+#[check]
+async fn renumber_flag_p(ctx: &Context, msg: &Message) -> Result<(), Reason> {
+    crate::modules::perms::check_perms(ctx, msg, "p").await
+}
+
+#[command("renumber")]
+#[only_in("guilds")]
+#[checks(renumber_flag_p)]
+/// Renumbers the guild's memes so that their ids have no gaps. Old ids keep
+/// working in `!meme <id>`, along with a notice of the new id.
+async fn renumber_memes(ctx: &Context, msg: &Message) -> CommandResult {
+    let mut conn = Connection::open(crate::config::DB_PATH)?;
+    let guild = *msg.guild_id.unwrap().as_u64();
+    sql::table(&conn, guild)?;
+
+    let changes = renumber::renumber(&mut conn, guild)?;
+    if changes.is_empty() {
+        msg.channel_id
+            .say(&ctx.http, "meme ids have no gaps, nothing to do")
+            .await?;
+        return Ok(());
+    }
+
+    let lines: Vec<_> = changes
+        .iter()
+        .map(|(old, new)| format!("{} -> {}", old, new))
+        .collect();
+    let header = format!("renumbered {} memes:", changes.len());
+    paginate(ctx, msg, &header, &lines).await
+}
+
+#[command("dupes")]
+#[only_in("guilds")]
+#[checks(edit_memes_check)]
+/// Lists groups of memes that are likely duplicates of each other.
+async fn list_dupes(ctx: &Context, msg: &Message) -> CommandResult {
+    let conn = Connection::open(crate::config::DB_PATH)?;
+    let table = sql::table(&conn, *msg.guild_id.unwrap().as_u64())?;
+    let memes: Vec<_> = sql::all_memes(&conn, &table)?
+        .into_iter()
+        .map(|x| (x.id, x.text))
+        .collect();
+
+    // comparing every pair of memes takes a while on big lists
+    let clusters = tokio::task::spawn_blocking(move || dupes::clusters(&memes)).await?;
+
+    if clusters.is_empty() {
+        msg.channel_id
+            .say(&ctx.http, "no likely duplicates found")
+            .await?;
+        return Ok(());
+    }
+
+    let lines: Vec<_> = clusters
+        .iter()
+        .map(|i| {
+            i.iter()
+                .map(|x| x.to_string())
+                .collect::<Vec<_>>()
+                .join(", ")
+        })
+        .collect();
+    paginate(ctx, msg, "likely duplicates:", &lines).await
+}
+
+#[command("preview")]
+#[min_args(1)]
+#[only_in("guilds")]
+#[usage("<id|name> [args...]")]
+/// Shows what a meme looks like with its placeholders filled in. Memes flagged
+/// as templates with `!flagmeme <id> template` can use `{user}`, `{author}`,
+/// `{random_member}`, `{channel}`, `{date}` and `{1}`, `{2}`, ... for
+/// arguments. Use `{{` and `}}` for literal braces.
+async fn preview_meme(ctx: &Context, msg: &Message, mut args: Args) -> CommandResult {
+    let conn = Connection::open(crate::config::DB_PATH)?;
+    let table = sql::table(&conn, *msg.guild_id.unwrap().as_u64())?;
+    let query: String = args.single_quoted()?;
+    let values: Vec<String> = args.quoted().iter().filter_map(|x| x.ok()).collect();
+
+    let res = match i32::from_str(&query) {
+        Ok(x) => sql::meme_by_id(&conn, &table, x),
+        Err(_) => sql::meme_by_name(&conn, &table, &query.to_lowercase()),
+    };
+
+    match res {
+        Ok(x) if x.nsfw && !nsfw_allowed(ctx, msg).await => drop(
+            msg.channel_id
+                .say(
+                    &ctx.http,
+                    format!(
+                        "meme {} is nsfw and can only be shown in nsfw channels",
+                        x.id
+                    ),
+                )
+                .await?,
+        ),
+        Ok(x) => {
+            let text = render(ctx, msg, &x, &values).await;
+            msg.channel_id
+                .send_message(&ctx.http, |m| {
+                    m.allowed_mentions(|x| x.parse(ParseValue::Users));
+                    m.content(text)
+                })
+                .await?;
+        }
+        Err(_) => drop(
+            msg.channel_id
+                .say(&ctx.http, format!("meme {} not found", query))
+                .await?,
+        ),
+    }
+    Ok(())
+}
+
+#[command("names")]
+#[only_in("guilds")]
+/// Lists all named memes.
+async fn list_names(ctx: &Context, msg: &Message) -> CommandResult {
+    let conn = Connection::open(crate::config::DB_PATH)?;
+    let table = sql::table(&conn, *msg.guild_id.unwrap().as_u64())?;
+    let nsfw = nsfw_allowed(ctx, msg).await;
+
+    let lines: Vec<_> = sql::all_memes(&conn, &table)?
+        .iter()
+        .filter_map(|x| {
+            let name = x.name.as_ref()?;
+            Some(format!("{} {} {}", name, x.id, listing(x, nsfw)))
+        })
+        .collect();
+
+    if lines.is_empty() {
+        msg.channel_id.say(&ctx.http, "no memes have names").await?;
+        return Ok(());
+    }
+    paginate(ctx, msg, "", &lines).await
+}
+
+#[command("list")]
+#[aliases(ls)]
+#[only_in("guilds")]
+/// Lists all memes along with their ids.
+async fn list_memes(ctx: &Context, msg: &Message) -> CommandResult {
+    let conn = Connection::open(crate::config::DB_PATH)?;
+    let table = sql::table(&conn, *msg.guild_id.unwrap().as_u64())?;
+    let nsfw = nsfw_allowed(ctx, msg).await;
+
+    let lines: Vec<_> = sql::all_memes(&conn, &table)?
+        .iter()
+        .map(|x| format!("{} {}", x.id, listing(x, nsfw)))
+        .collect();
+    paginate(ctx, msg, "", &lines).await
+}
+
 #[check]
 pub async fn edit_memes_check(ctx: &Context, msg: &Message) -> Result<(), Reason> {
     crate::modules::perms::check_perms(ctx, msg, "m").await
@@ -117,12 +1189,48 @@ pub async fn edit_memes_check(ctx: &Context, msg: &Message) -> Result<(), Reason
 
 #[group]
 #[only_in("guilds")]
-#[commands(meme, addmeme, delmeme)]
+#[commands(
+    meme, addmeme, submitmeme, namememe, tagmeme, saidby, editmeme, delmeme, lockmeme, flagmeme,
+    scopememe, markov
+)]
 pub struct Memes;
 
+#[group]
+#[prefix("memes")]
+#[only_in("guilds")]
+#[commands(
+    list_memes,
+    list_names,
+    preview_meme,
+    show_stats,
+    import,
+    import_pins,
+    list_dupes,
+    renumber_memes,
+    show_quota
+)]
+/// The memes group contains commands that work on the meme list as a whole.
+///
+/// `!memes list` - lists every meme with its id
+/// `!memes names` - lists every named meme
+/// `!memes preview <id|name> [args...]` - shows a meme with its placeholders filled in
+/// `!memes stats` - shows meme usage statistics
+/// `!memes quota` - shows how many more memes you can add and submit
+///
+/// The following commands require the `m` permission flag.
+///
+/// `!memes import` - adds every meme in an attached text, csv or json file
+/// `!memes importpins <channel>` - adds every message pinned in a channel
+/// `!memes dupes` - lists groups of memes that look like duplicates
+///
+/// The following commands require the `p` permission flag.
+///
+/// `!memes renumber` - removes the gaps from meme ids
+pub struct MemeTools;
+
 mod sql {
     use super::*;
-    use rusqlite::{params, Connection, Result};
+    use rusqlite::{params, Connection, Result, Row};
 
     pub fn table(conn: &Connection, id: u64) -> Result<String> {
         let table = format!("x{}_memes", id);
@@ -137,70 +1245,289 @@ mod sql {
             params![],
         )?;
 
+        // columns added after the table was first created, older tables are
+        // migrated in place
+        for (name, kind) in COLUMNS {
+            let exists: i64 = conn.query_row(
+                "SELECT count(*) FROM pragma_table_info(?) WHERE name=?",
+                params![table, name],
+                |row| row.get(0),
+            )?;
+            if exists == 0 {
+                conn.execute(
+                    &format!("ALTER TABLE \"{}\" ADD COLUMN {} {}", table, name, kind),
+                    params![],
+                )?;
+            }
+        }
+
+        conn.execute(
+            &format!(
+                "CREATE UNIQUE INDEX IF NOT EXISTS \"{0}_name\" ON \"{0}\" (name)",
+                table
+            ),
+            params![],
+        )?;
+
         Ok(table)
     }
 
-    pub fn random_meme(conn: &Connection, table: &str) -> Result<Meme> {
+    const COLUMNS: &[(&str, &str)] = &[
+        ("author", "CHAR(32)"),
+        ("name", "CHAR(32)"),
+        ("said_by", "CHAR(32)"),
+        ("locked", "INT"),
+        ("nsfw", "INT"),
+        ("spoiler", "INT"),
+        ("channel", "CHAR(32)"),
+        ("source", "TEXT"),
+        ("template", "INT"),
+        ("tags", "TEXT"),
+    ];
+
+    /// The columns read by `meme_from_row`, in order.
+    const FIELDS: &str = "id, time, text, author, name, said_by, locked, nsfw, spoiler, channel, \
+                          source, template, tags";
+
+    /// Limits a query to memes that can be sent, given whether NSFW memes are
+    /// allowed. Takes that as a parameter.
+    const ALLOWED: &str = "(? OR NOT coalesce(nsfw, 0))";
+
+    /// Limits a query to a single pool of memes: the ones belonging to a
+    /// channel, or an empty string for the guild's. Takes that as a parameter.
+    const POOL: &str = "coalesce(channel, '') = ?";
+
+    fn pool(channel: Option<u64>) -> String {
+        channel.map(|x| x.to_string()).unwrap_or_default()
+    }
+
+    fn meme_from_row(row: &Row) -> Result<Meme> {
+        Ok(Meme {
+            id: row.get(0)?,
+            time: row.get::<usize, Option<i64>>(1)?.unwrap_or(0),
+            text: row.get(2)?,
+            author: row
+                .get::<usize, Option<String>>(3)?
+                .and_then(|x| u64::from_str(&x).ok()),
+            name: row.get(4)?,
+            said_by: row
+                .get::<usize, Option<String>>(5)?
+                .and_then(|x| u64::from_str(&x).ok()),
+            locked: row.get::<usize, Option<bool>>(6)?.unwrap_or(false),
+            nsfw: row.get::<usize, Option<bool>>(7)?.unwrap_or(false),
+            spoiler: row.get::<usize, Option<bool>>(8)?.unwrap_or(false),
+            channel: row
+                .get::<usize, Option<String>>(9)?
+                .and_then(|x| u64::from_str(&x).ok()),
+            source: row.get(10)?,
+            template: row.get::<usize, Option<bool>>(11)?.unwrap_or(false),
+            tags: row
+                .get::<usize, Option<String>>(12)?
+                .map(|x| x.split_whitespace().map(|x| x.to_string()).collect())
+                .unwrap_or_default(),
+        })
+    }
+
+    pub fn random_meme(
+        conn: &Connection,
+        table: &str,
+        channel: Option<u64>,
+        nsfw: bool,
+    ) -> Result<Meme> {
         conn.query_row(
             &format!(
-                "SELECT * FROM \"{0}\"
+                "SELECT {1} FROM \"{0}\" WHERE {2} AND {3}
                      LIMIT 1 OFFSET 
                          abs(random()) 
-                             % (SELECT count(*) FROM \"{0}\")",
-                table,
+                             % (SELECT count(*) FROM \"{0}\" WHERE {2} AND {3})",
+                table, FIELDS, POOL, ALLOWED,
             ),
-            params![],
-            |row| {
-                Ok(Meme {
-                    id: row.get(0)?,
-                    text: row.get(2)?,
-                })
-            },
+            params![pool(channel), nsfw, pool(channel), nsfw],
+            meme_from_row,
         )
     }
 
     pub fn meme_by_id(conn: &Connection, table: &str, id: i32) -> Result<Meme> {
         conn.query_row(
-            &format!("SELECT * FROM \"{0}\" WHERE id=?", table),
+            &format!("SELECT {1} FROM \"{0}\" WHERE id=?", table, FIELDS),
             params![id],
-            |row| {
-                Ok(Meme {
-                    id: row.get(0)?,
-                    text: row.get(2)?,
-                })
-            },
+            meme_from_row,
+        )
+    }
+
+    pub fn meme_by_name(conn: &Connection, table: &str, name: &str) -> Result<Meme> {
+        conn.query_row(
+            &format!("SELECT {1} FROM \"{0}\" WHERE name=?", table, FIELDS),
+            params![name],
+            meme_from_row,
+        )
+    }
+
+    pub fn set_name(conn: &Connection, table: &str, id: i32, name: Option<&str>) -> Result<()> {
+        conn.execute(
+            &format!("UPDATE \"{}\" SET name=? WHERE id=?", table),
+            params![name, id],
+        )?;
+
+        Ok(())
+    }
+
+    pub fn set_said_by(conn: &Connection, table: &str, id: i32, user: Option<u64>) -> Result<()> {
+        conn.execute(
+            &format!("UPDATE \"{}\" SET said_by=? WHERE id=?", table),
+            params![user.map(|x| x.to_string()), id],
+        )?;
+
+        Ok(())
+    }
+
+    pub fn set_text(conn: &Connection, table: &str, id: i32, text: &str) -> Result<()> {
+        conn.execute(
+            &format!("UPDATE \"{}\" SET text=? WHERE id=?", table),
+            params![text, id],
+        )?;
+
+        Ok(())
+    }
+
+    /// Sets a meme's tags, which are stored separated by spaces.
+    pub fn set_tags(conn: &Connection, table: &str, id: i32, tags: &[String]) -> Result<()> {
+        conn.execute(
+            &format!("UPDATE \"{}\" SET tags=? WHERE id=?", table),
+            params![Some(tags.join(" ")).filter(|x| !x.is_empty()), id],
+        )?;
+
+        Ok(())
+    }
+
+    /// Sets one of the `nsfw`, `spoiler` or `template` flags.
+    pub fn set_flag(
+        conn: &Connection,
+        table: &str,
+        id: i32,
+        flag: &str,
+        value: bool,
+    ) -> Result<()> {
+        conn.execute(
+            &format!("UPDATE \"{}\" SET {}=? WHERE id=?", table, flag),
+            params![value, id],
+        )?;
+
+        Ok(())
+    }
+
+    /// Sets the link to the message a meme was taken from.
+    pub fn set_source(conn: &Connection, table: &str, id: i32, source: &str) -> Result<()> {
+        conn.execute(
+            &format!("UPDATE \"{}\" SET source=? WHERE id=?", table),
+            params![source, id],
+        )?;
+
+        Ok(())
+    }
+
+    pub fn meme_by_source(conn: &Connection, table: &str, source: &str) -> Result<Meme> {
+        conn.query_row(
+            &format!("SELECT {1} FROM \"{0}\" WHERE source=?", table, FIELDS),
+            params![source],
+            meme_from_row,
+        )
+    }
+
+    pub fn set_channel(
+        conn: &Connection,
+        table: &str,
+        id: i32,
+        channel: Option<u64>,
+    ) -> Result<()> {
+        conn.execute(
+            &format!("UPDATE \"{}\" SET channel=? WHERE id=?", table),
+            params![channel.map(|x| x.to_string()), id],
+        )?;
+
+        Ok(())
+    }
+
+    /// The number of memes in a pool, see `POOL`.
+    pub fn pool_size(conn: &Connection, table: &str, channel: Option<u64>) -> Result<i64> {
+        conn.query_row(
+            &format!("SELECT count(*) FROM \"{}\" WHERE {}", table, POOL),
+            params![pool(channel)],
+            |row| row.get(0),
+        )
+    }
+
+    pub fn set_locked(conn: &Connection, table: &str, id: i32, locked: bool) -> Result<()> {
+        conn.execute(
+            &format!("UPDATE \"{}\" SET locked=? WHERE id=?", table),
+            params![locked, id],
+        )?;
+
+        Ok(())
+    }
+
+    /// A random meme that has been tagged with who said it.
+    pub fn random_said_meme(conn: &Connection, table: &str, nsfw: bool) -> Result<Meme> {
+        conn.query_row(
+            &format!(
+                "SELECT {1} FROM \"{0}\" WHERE said_by IS NOT NULL AND {2}
+                     ORDER BY random() LIMIT 1",
+                table, FIELDS, ALLOWED
+            ),
+            params![nsfw],
+            meme_from_row,
         )
     }
 
+    /// The id a meme was given by `!memes renumber`, if it was renumbered.
+    pub fn renumbered(conn: &Connection, guild: u64, old: i32) -> Result<Option<i32>> {
+        let table = renumber::mapping_table(conn, guild)?;
+        match conn.query_row(
+            &format!("SELECT new FROM \"{}\" WHERE old=?", table),
+            params![old],
+            |row| row.get(0),
+        ) {
+            Ok(x) => Ok(Some(x)),
+            Err(rusqlite::Error::QueryReturnedNoRows) => Ok(None),
+            Err(x) => Err(x),
+        }
+    }
+
     pub fn latest_meme(conn: &Connection, table: &str) -> Result<Meme> {
         conn.query_row(
-            &format!("SELECT * FROM \"{0}\" ORDER BY id DESC LIMIT 1", table),
+            &format!(
+                "SELECT {1} FROM \"{0}\" ORDER BY id DESC LIMIT 1",
+                table, FIELDS
+            ),
             params![],
-            |row| {
-                Ok(Meme {
-                    id: row.get(0)?,
-                    text: row.get(2)?,
-                })
-            },
+            meme_from_row,
         )
     }
 
-    pub fn search_meme(conn: &Connection, table: &str, query: &str) -> Result<Meme> {
+    pub fn search_meme(
+        conn: &Connection,
+        table: &str,
+        query: &str,
+        channel: Option<u64>,
+        nsfw: bool,
+    ) -> Result<Meme> {
         conn.query_row(
             &format!(
-                "SELECT * FROM \"{0}\" WHERE text LIKE ? ORDER BY random()",
-                table
+                "SELECT {1} FROM \"{0}\" WHERE text LIKE ? AND {2} AND {3} ORDER BY random()",
+                table, FIELDS, POOL, ALLOWED
             ),
-            params![&format!("%{}%", query)],
-            |row| {
-                Ok(Meme {
-                    id: row.get(0)?,
-                    text: row.get(2)?,
-                })
-            },
+            params![&format!("%{}%", query), pool(channel), nsfw],
+            meme_from_row,
         )
     }
 
+    pub fn all_memes(conn: &Connection, table: &str) -> Result<Vec<Meme>> {
+        let mut stmt = conn.prepare(&format!("SELECT {1} FROM \"{0}\"", table, FIELDS))?;
+        let iter = stmt.query_map(params![], meme_from_row)?;
+
+        Ok(iter.filter_map(|i| i.ok()).collect())
+    }
+
     pub fn get_seq(conn: &Connection, table: &str) -> Result<i32> {
         conn.query_row(
             "SELECT seq FROM sqlite_sequence WHERE name=?",
@@ -227,12 +1554,79 @@ mod sql {
         Ok(())
     }
 
-    pub fn add_meme(conn: &Connection, table: &str, time: i64, text: &str) -> Result<()> {
+    pub fn add_meme(
+        conn: &Connection,
+        table: &str,
+        time: i64,
+        text: &str,
+        author: u64,
+        channel: Option<u64>,
+    ) -> Result<()> {
         conn.execute(
-            &format!("INSERT INTO \"{}\" (time, text) VALUES (?, ?)", table),
-            params![time, text],
+            &format!(
+                "INSERT INTO \"{}\" (time, text, author, channel) VALUES (?, ?, ?, ?)",
+                table
+            ),
+            params![
+                time,
+                text,
+                author.to_string(),
+                channel.map(|x| x.to_string())
+            ],
         )?;
+        super::renumber::reused(conn, table, conn.last_insert_rowid() as i32)?;
 
         Ok(())
     }
 }
+
+#[cfg(test)]
+pub mod test {
+    use super::*;
+
+    #[test]
+    fn reserved_names() {
+        assert!(valid_name("copypasta"));
+        assert!(!valid_name("global"));
+        assert!(!valid_name("onthisday"));
+        assert!(!valid_name("42"));
+    }
+
+    #[test]
+    fn channel_pools() {
+        let conn = Connection::open_in_memory().unwrap();
+        let table = sql::table(&conn, 1).unwrap();
+        sql::add_meme(&conn, &table, 0, "guild meme", 2, None).unwrap();
+        sql::add_meme(&conn, &table, 0, "channel meme", 2, Some(3)).unwrap();
+
+        assert_eq!(sql::pool_size(&conn, &table, None).unwrap(), 1);
+        assert_eq!(sql::pool_size(&conn, &table, Some(3)).unwrap(), 1);
+        assert_eq!(sql::pool_size(&conn, &table, Some(4)).unwrap(), 0);
+        for _ in 0..10 {
+            let res = sql::random_meme(&conn, &table, None, false).unwrap();
+            assert_eq!(res.id, 1);
+            let res = sql::random_meme(&conn, &table, Some(3), false).unwrap();
+            assert_eq!(res.id, 2);
+        }
+        assert!(sql::search_meme(&conn, &table, "channel", None, false).is_err());
+        let res = sql::search_meme(&conn, &table, "meme", Some(3), false).unwrap();
+        assert_eq!(res.id, 2);
+    }
+
+    #[test]
+    fn nsfw_filtering() {
+        let conn = Connection::open_in_memory().unwrap();
+        let table = sql::table(&conn, 1).unwrap();
+        sql::add_meme(&conn, &table, 0, "safe meme", 2, None).unwrap();
+        sql::add_meme(&conn, &table, 0, "nsfw meme", 2, None).unwrap();
+        sql::set_flag(&conn, &table, 2, "nsfw", true).unwrap();
+
+        for _ in 0..10 {
+            let res = sql::random_meme(&conn, &table, None, false).unwrap();
+            assert_eq!(res.id, 1);
+        }
+        assert!(sql::search_meme(&conn, &table, "nsfw", None, false).is_err());
+        let res = sql::search_meme(&conn, &table, "nsfw", None, true).unwrap();
+        assert_eq!(res.id, 2);
+    }
+}
@@ -0,0 +1,421 @@
+use rusqlite::Connection;
+use serenity::client::Context;
+use serenity::framework::standard::macros::{command, group};
+use serenity::framework::standard::Args;
+use serenity::framework::standard::CommandResult;
+use serenity::model::channel::Message;
+
+use super::{listing, nsfw_allowed, send_meme, valid_name};
+use crate::misc::paginate;
+use crate::modules::settings;
+
+/// Whether the invoker may change a collection. Members with the `m` flag can
+/// change any collection, everyone else only the ones they created.
+async fn can_change(ctx: &Context, msg: &Message, author: u64) -> bool {
+    author == *msg.author.id.as_u64()
+        || crate::modules::perms::check_perms(ctx, msg, "m")
+            .await
+            .is_ok()
+}
+
+/// Whether the invoker may change a collection given its creator, telling them
+/// if it doesn't exist or they aren't allowed to.
+async fn changeable(ctx: &Context, msg: &Message, name: &str, author: Option<u64>) -> bool {
+    let res = match author {
+        Some(x) if can_change(ctx, msg, x).await => return true,
+        Some(_) => format!("only whoever created {} can change it", name),
+        None => format!("no collection named {}", name),
+    };
+    let _ = msg.channel_id.say(&ctx.http, res).await;
+    false
+}
+
+#[command]
+#[aliases(ls)]
+#[only_in("guilds")]
+/// Lists the guild's collections.
+async fn list(ctx: &Context, msg: &Message) -> CommandResult {
+    let conn = Connection::open(crate::config::DB_PATH)?;
+    let guild = *msg.guild_id.unwrap().as_u64();
+    let lines: Vec<_> = sql::all_collections(&conn, guild)?
+        .into_iter()
+        .map(|(name, size)| format!("{} ({} memes)", name, size))
+        .collect();
+
+    if lines.is_empty() {
+        msg.channel_id
+            .say(&ctx.http, "no collections have been created yet")
+            .await?;
+        return Ok(());
+    }
+    paginate(ctx, msg, "", &lines).await
+}
+
+#[command]
+#[num_args(1)]
+#[only_in("guilds")]
+#[usage("<name>")]
+/// Creates an empty collection.
+async fn create(ctx: &Context, msg: &Message, args: Args) -> CommandResult {
+    let conn = Connection::open(crate::config::DB_PATH)?;
+    let guild = *msg.guild_id.unwrap().as_u64();
+    let name = args.rest().trim().to_lowercase();
+
+    let res = if !valid_name(&name) {
+        "names must be a single word of letters, numbers, `-` or `_` and can't be a number, \
+         `global` or `onthisday`"
+            .to_string()
+    } else if sql::author(&conn, guild, &name).is_ok() {
+        format!("{} already exists", name)
+    } else {
+        sql::create(&conn, guild, &name, *msg.author.id.as_u64())?;
+        format!("created {}", name)
+    };
+
+    msg.channel_id.say(&ctx.http, res).await?;
+    Ok(())
+}
+
+#[command]
+#[min_args(2)]
+#[only_in("guilds")]
+#[usage("<name> <id...>")]
+/// Adds memes to the end of a collection, in the order given.
+async fn add(ctx: &Context, msg: &Message, mut args: Args) -> CommandResult {
+    let conn = Connection::open(crate::config::DB_PATH)?;
+    let guild = *msg.guild_id.unwrap().as_u64();
+    let table = super::sql::table(&conn, guild)?;
+    let name = args.single::<String>()?.to_lowercase();
+    let author = sql::author(&conn, guild, &name).ok();
+    if !changeable(ctx, msg, &name, author).await {
+        return Ok(());
+    }
+
+    let mut ids = vec![];
+    for i in args.iter::<i32>() {
+        let id = i?;
+        if super::sql::meme_by_id(&conn, &table, id).is_err() {
+            msg.channel_id
+                .say(&ctx.http, format!("meme {} not found", id))
+                .await?;
+            return Ok(());
+        }
+        ids.push(id);
+    }
+    for i in ids.iter() {
+        sql::add_meme(&conn, guild, &name, *i)?;
+    }
+
+    msg.channel_id
+        .say(&ctx.http, format!("added {} memes to {}", ids.len(), name))
+        .await?;
+    Ok(())
+}
+
+#[command]
+#[aliases(rm, del)]
+#[num_args(2)]
+#[only_in("guilds")]
+#[usage("<name> <id>")]
+/// Removes a meme from a collection.
+async fn remove(ctx: &Context, msg: &Message, mut args: Args) -> CommandResult {
+    let conn = Connection::open(crate::config::DB_PATH)?;
+    let guild = *msg.guild_id.unwrap().as_u64();
+    let name = args.single::<String>()?.to_lowercase();
+    let id: i32 = args.single()?;
+    let author = sql::author(&conn, guild, &name).ok();
+    if !changeable(ctx, msg, &name, author).await {
+        return Ok(());
+    }
+
+    let res = if sql::del_meme(&conn, guild, &name, id)? {
+        format!("removed meme {} from {}", id, name)
+    } else {
+        format!("meme {} isn't in {}", id, name)
+    };
+    msg.channel_id.say(&ctx.http, res).await?;
+    Ok(())
+}
+
+#[command("drop")]
+#[num_args(1)]
+#[only_in("guilds")]
+#[usage("<name>")]
+/// Deletes a collection. The memes in it are kept.
+async fn drop_collection(ctx: &Context, msg: &Message, args: Args) -> CommandResult {
+    let mut conn = Connection::open(crate::config::DB_PATH)?;
+    let guild = *msg.guild_id.unwrap().as_u64();
+    let name = args.rest().trim().to_lowercase();
+    let author = sql::author(&conn, guild, &name).ok();
+    if !changeable(ctx, msg, &name, author).await {
+        return Ok(());
+    }
+
+    sql::drop(&mut conn, guild, &name)?;
+    msg.channel_id
+        .say(&ctx.http, format!("deleted {}", name))
+        .await?;
+    Ok(())
+}
+
+#[command]
+#[num_args(1)]
+#[only_in("guilds")]
+#[usage("<name>")]
+/// Sends the next meme in a collection, starting over after the last one.
+async fn play(ctx: &Context, msg: &Message, args: Args) -> CommandResult {
+    let conn = Connection::open(crate::config::DB_PATH)?;
+    let guild = *msg.guild_id.unwrap().as_u64();
+    let table = super::sql::table(&conn, guild)?;
+    let embed = settings::get_bool(&conn, guild, "meme_embed")?;
+    let name = args.rest().trim().to_lowercase();
+
+    let ids = sql::memes(&conn, guild, &name)?;
+    let cursor = match sql::cursor(&conn, guild, &name) {
+        Ok(_) if ids.is_empty() => {
+            let res = format!("{} is empty", name);
+            msg.channel_id.say(&ctx.http, res).await?;
+            return Ok(());
+        }
+        Ok(x) => x % ids.len(),
+        Err(_) => {
+            let res = format!("no collection named {}", name);
+            msg.channel_id.say(&ctx.http, res).await?;
+            return Ok(());
+        }
+    };
+    sql::set_cursor(&conn, guild, &name, (cursor + 1) % ids.len())?;
+
+    let meme = super::sql::meme_by_id(&conn, &table, ids[cursor]);
+    msg.channel_id
+        .say(
+            &ctx.http,
+            format!("{} ({} of {})", name, cursor + 1, ids.len()),
+        )
+        .await?;
+    match meme {
+        Ok(x) => send_meme(ctx, msg, &x, &[], embed, None).await?,
+        Err(_) => drop(
+            msg.channel_id
+                .say(&ctx.http, format!("meme {} not found", ids[cursor]))
+                .await?,
+        ),
+    }
+    Ok(())
+}
+
+#[command]
+#[num_args(1)]
+#[only_in("guilds")]
+#[usage("<name>")]
+/// Lists the memes in a collection, in order.
+async fn show(ctx: &Context, msg: &Message, args: Args) -> CommandResult {
+    let conn = Connection::open(crate::config::DB_PATH)?;
+    let guild = *msg.guild_id.unwrap().as_u64();
+    let table = super::sql::table(&conn, guild)?;
+    let name = args.rest().trim().to_lowercase();
+
+    if sql::author(&conn, guild, &name).is_err() {
+        msg.channel_id
+            .say(&ctx.http, format!("no collection named {}", name))
+            .await?;
+        return Ok(());
+    }
+
+    let nsfw = nsfw_allowed(ctx, msg).await;
+    let lines: Vec<_> = sql::memes(&conn, guild, &name)?
+        .into_iter()
+        .enumerate()
+        .map(|(i, id)| match super::sql::meme_by_id(&conn, &table, id) {
+            Ok(x) => format!("{}. {} {}", i + 1, id, listing(&x, nsfw)),
+            Err(_) => format!("{}. {} (not found)", i + 1, id),
+        })
+        .collect();
+    paginate(ctx, msg, &format!("{}:", name), &lines).await
+}
+
+#[group]
+#[prefix("collection")]
+#[only_in("guilds")]
+#[commands(list, create, add, remove, drop_collection, play, show)]
+/// Collections are named, ordered lists of memes, such as a best of the year.
+/// Anyone can create one; only its creator or members with the `m` flag can
+/// change it.
+///
+/// `!collection list` - lists the guild's collections
+/// `!collection create <name>` - creates an empty collection
+/// `!collection add <name> <id...>` - adds memes to the end of a collection
+/// `!collection remove <name> <id>` - removes a meme from a collection
+/// `!collection drop <name>` - deletes a collection
+/// `!collection play <name>` - sends the next meme in a collection
+/// `!collection show <name>` - lists the memes in a collection
+pub struct Collection;
+
+pub mod sql {
+    use rusqlite::{params, Connection, Result};
+
+    /// Creates the tables for a guild's collections, returning the names of
+    /// the table of collections and the table of their memes.
+    pub fn tables(conn: &Connection, id: u64) -> Result<(String, String)> {
+        let collections = format!("x{}_collections", id);
+        let memes = format!("x{}_collection_memes", id);
+        conn.execute(
+            &format!(
+                "CREATE TABLE IF NOT EXISTS \"{}\" (
+                    name CHAR(32) UNIQUE,
+                    author CHAR(32),
+                    cursor INTEGER DEFAULT 0)",
+                collections
+            ),
+            params![],
+        )?;
+        conn.execute(
+            &format!(
+                "CREATE TABLE IF NOT EXISTS \"{}\" (
+                    name CHAR(32),
+                    position INTEGER,
+                    meme INTEGER)",
+                memes
+            ),
+            params![],
+        )?;
+
+        Ok((collections, memes))
+    }
+
+    pub fn create(conn: &Connection, guild: u64, name: &str, author: u64) -> Result<()> {
+        let (collections, _) = tables(conn, guild)?;
+        conn.execute(
+            &format!(
+                "INSERT INTO \"{}\" (name, author) VALUES (?, ?)",
+                collections
+            ),
+            params![name, author.to_string()],
+        )?;
+
+        Ok(())
+    }
+
+    pub fn author(conn: &Connection, guild: u64, name: &str) -> Result<u64> {
+        let (collections, _) = tables(conn, guild)?;
+        conn.query_row(
+            &format!("SELECT author FROM \"{}\" WHERE name=?", collections),
+            params![name],
+            |row| Ok(row.get::<usize, String>(0)?.parse().unwrap_or_default()),
+        )
+    }
+
+    /// Every collection with how many memes it has.
+    pub fn all_collections(conn: &Connection, guild: u64) -> Result<Vec<(String, i64)>> {
+        let (collections, memes) = tables(conn, guild)?;
+        let mut stmt = conn.prepare(&format!(
+            "SELECT c.name, count(m.meme) FROM \"{}\" c LEFT JOIN \"{}\" m ON m.name = c.name
+                GROUP BY c.name ORDER BY c.name",
+            collections, memes
+        ))?;
+        let iter = stmt.query_map(params![], |row| Ok((row.get(0)?, row.get(1)?)))?;
+
+        Ok(iter.filter_map(|i| i.ok()).collect())
+    }
+
+    /// The ids of the memes in a collection, in order.
+    pub fn memes(conn: &Connection, guild: u64, name: &str) -> Result<Vec<i32>> {
+        let (_, memes) = tables(conn, guild)?;
+        let mut stmt = conn.prepare(&format!(
+            "SELECT meme FROM \"{}\" WHERE name=? ORDER BY position",
+            memes
+        ))?;
+        let iter = stmt.query_map(params![name], |row| row.get(0))?;
+
+        Ok(iter.filter_map(|i| i.ok()).collect())
+    }
+
+    pub fn add_meme(conn: &Connection, guild: u64, name: &str, meme: i32) -> Result<()> {
+        let (_, memes) = tables(conn, guild)?;
+        conn.execute(
+            &format!(
+                "INSERT INTO \"{0}\" (name, position, meme) VALUES (?, (SELECT coalesce(max(position), 0) + 1 FROM \"{0}\" WHERE name=?), ?)",
+                memes
+            ),
+            params![name, name, meme],
+        )?;
+
+        Ok(())
+    }
+
+    /// Removes a meme from a collection, returning whether it was in it.
+    pub fn del_meme(conn: &Connection, guild: u64, name: &str, meme: i32) -> Result<bool> {
+        let (_, memes) = tables(conn, guild)?;
+        let n = conn.execute(
+            &format!("DELETE FROM \"{}\" WHERE name=? AND meme=?", memes),
+            params![name, meme],
+        )?;
+
+        Ok(n > 0)
+    }
+
+    /// Removes a meme from every collection.
+    pub fn del_meme_entries(conn: &Connection, guild: u64, meme: i32) -> Result<()> {
+        let (_, memes) = tables(conn, guild)?;
+        conn.execute(
+            &format!("DELETE FROM \"{}\" WHERE meme=?", memes),
+            params![meme],
+        )?;
+
+        Ok(())
+    }
+
+    pub fn drop(conn: &mut Connection, guild: u64, name: &str) -> Result<()> {
+        let (collections, memes) = tables(conn, guild)?;
+        let tx = conn.transaction()?;
+        tx.execute(
+            &format!("DELETE FROM \"{}\" WHERE name=?", memes),
+            params![name],
+        )?;
+        tx.execute(
+            &format!("DELETE FROM \"{}\" WHERE name=?", collections),
+            params![name],
+        )?;
+        tx.commit()
+    }
+
+    pub fn cursor(conn: &Connection, guild: u64, name: &str) -> Result<usize> {
+        let (collections, _) = tables(conn, guild)?;
+        conn.query_row(
+            &format!("SELECT cursor FROM \"{}\" WHERE name=?", collections),
+            params![name],
+            |row| Ok(row.get::<usize, i64>(0)? as usize),
+        )
+    }
+
+    pub fn set_cursor(conn: &Connection, guild: u64, name: &str, cursor: usize) -> Result<()> {
+        let (collections, _) = tables(conn, guild)?;
+        conn.execute(
+            &format!("UPDATE \"{}\" SET cursor=? WHERE name=?", collections),
+            params![cursor as i64, name],
+        )?;
+
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+pub mod test {
+    use super::*;
+
+    #[test]
+    fn keeps_order() {
+        let conn = Connection::open_in_memory().unwrap();
+        sql::create(&conn, 1, "best", 2).unwrap();
+        for i in [42, 7, 57].iter() {
+            sql::add_meme(&conn, 1, "best", *i).unwrap();
+        }
+        sql::del_meme(&conn, 1, "best", 7).unwrap();
+        sql::add_meme(&conn, 1, "best", 7).unwrap();
+        assert_eq!(sql::memes(&conn, 1, "best").unwrap(), vec![42, 57, 7]);
+        assert_eq!(
+            sql::all_collections(&conn, 1).unwrap(),
+            vec![("best".to_string(), 3)]
+        );
+    }
+}
@@ -0,0 +1,86 @@
+use chrono::TimeZone;
+use rusqlite::Connection;
+use serenity::client::Context;
+use serenity::framework::standard::CommandResult;
+use serenity::model::id::ChannelId;
+use std::collections::HashMap;
+
+use super::stats::{self, user_tag};
+use super::{channel_nsfw, listing};
+use crate::misc::pages;
+use crate::modules::schedule;
+use crate::modules::settings;
+
+const TOP: usize = 5;
+
+/// Posts a summary of the memes added and fetched since the last digest to the
+/// guild's `digest_channel`, if it has one and the digest is due. A digest
+/// that was missed is covered by the next one.
+pub async fn scheduled(ctx: &Context, guild: u64) -> CommandResult {
+    let conn = Connection::open(crate::config::DB_PATH)?;
+    let channel = match settings::get_channel(&conn, guild, "digest_channel")? {
+        Some(x) => x,
+        None => return Ok(()),
+    };
+    let period = settings::get_period(&conn, guild, "digest_day")?;
+    let end = match schedule::due(&conn, guild, "digest", period)? {
+        Some(x) => x,
+        None => return Ok(()),
+    };
+    let start = schedule::last_done(&conn, guild, "digest")?
+        .and_then(|x| end.timezone().timestamp_opt(x, 0).single())
+        .filter(|x| *x < end)
+        .unwrap_or_else(|| end - period.length());
+
+    let range = start.timestamp()..end.timestamp();
+
+    let table = super::sql::table(&conn, guild)?;
+    let fetches = stats::sql::table(&conn, guild)?;
+    let added: Vec<_> = super::sql::all_memes(&conn, &table)?
+        .into_iter()
+        .filter(|x| range.contains(&x.time))
+        .collect();
+    let requested = stats::sql::top(&conn, &fetches, "meme", range.clone(), TOP)?;
+
+    let mut contributors: HashMap<u64, usize> = HashMap::new();
+    for i in added.iter().filter_map(|x| x.author) {
+        *contributors.entry(i).or_default() += 1;
+    }
+    let mut contributors: Vec<_> = contributors.into_iter().collect();
+    contributors.sort_by_key(|x| std::cmp::Reverse(x.1));
+
+    let nsfw = channel_nsfw(ctx, ChannelId(channel)).await;
+    let mut lines = vec![format!("{} new memes", added.len())];
+    for i in added.iter() {
+        lines.push(format!("  {} {}", i.id, listing(i, nsfw)));
+    }
+    if !requested.is_empty() {
+        lines.push(String::new());
+        lines.push("most requested memes:".to_string());
+        for (id, count) in requested {
+            lines.push(format!("  meme {} - {} fetches", id, count));
+        }
+    }
+    if !contributors.is_empty() {
+        lines.push(String::new());
+        lines.push("top contributors:".to_string());
+        for (id, count) in contributors.into_iter().take(TOP) {
+            lines.push(format!(
+                "  {} - {} memes",
+                user_tag(ctx, &id.to_string()).await,
+                count
+            ));
+        }
+    }
+
+    let header = format!(
+        "meme digest for {} to {}:",
+        start.format("%Y-%m-%d"),
+        end.format("%Y-%m-%d")
+    );
+    for i in pages(&header, &lines) {
+        ChannelId(channel).say(&ctx.http, i).await?;
+    }
+    schedule::mark_done(&conn, guild, "digest", &end)?;
+    Ok(())
+}
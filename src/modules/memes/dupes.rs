@@ -0,0 +1,142 @@
+/// Memes at least this similar are considered near-duplicates.
+pub const THRESHOLD: f64 = 0.85;
+
+/// Lowercases the text, strips punctuation and collapses whitespace so that
+/// trivially different copies of a meme compare equal.
+pub fn normalize(text: &str) -> String {
+    text.chars()
+        .filter(|c| !c.is_ascii_punctuation())
+        .collect::<String>()
+        .to_lowercase()
+        .split_whitespace()
+        .collect::<Vec<_>>()
+        .join(" ")
+}
+
+/// Character bigrams of normalized text, sorted so two sets can be compared
+/// with a single merge.
+pub struct Fingerprint {
+    text: String,
+    bigrams: Vec<(char, char)>,
+}
+
+impl Fingerprint {
+    pub fn new(text: &str) -> Self {
+        let text = normalize(text);
+        let chars: Vec<char> = text.chars().collect();
+        let mut bigrams: Vec<_> = chars.windows(2).map(|x| (x[0], x[1])).collect();
+        bigrams.sort_unstable();
+        Self { text, bigrams }
+    }
+
+    /// Dice coefficient of the two bigram multisets, from 0 (nothing in
+    /// common) to 1 (identical).
+    pub fn similarity(&self, other: &Self) -> f64 {
+        let total = self.bigrams.len() + other.bigrams.len();
+        if total == 0 {
+            return if self.text == other.text { 1.0 } else { 0.0 };
+        }
+
+        let (mut i, mut j, mut common) = (0, 0, 0);
+        while i < self.bigrams.len() && j < other.bigrams.len() {
+            match self.bigrams[i].cmp(&other.bigrams[j]) {
+                std::cmp::Ordering::Less => i += 1,
+                std::cmp::Ordering::Greater => j += 1,
+                std::cmp::Ordering::Equal => {
+                    common += 1;
+                    i += 1;
+                    j += 1;
+                }
+            }
+        }
+
+        (2 * common) as f64 / total as f64
+    }
+
+    /// Cheap upper bound on `similarity`, used to skip comparing texts of
+    /// wildly different lengths.
+    fn could_match(&self, other: &Self) -> bool {
+        let (a, b) = (self.bigrams.len(), other.bigrams.len());
+        let bound = (2 * a.min(b)) as f64 / (a + b).max(1) as f64;
+        bound >= THRESHOLD || a + b == 0
+    }
+
+    pub fn is_exact(&self, other: &Self) -> bool {
+        self.text == other.text
+    }
+
+    pub fn is_near(&self, other: &Self) -> bool {
+        self.could_match(other) && self.similarity(other) >= THRESHOLD
+    }
+}
+
+/// Groups the given memes into clusters of likely duplicates. Clusters with a
+/// single meme are left out.
+pub fn clusters(memes: &[(i32, String)]) -> Vec<Vec<i32>> {
+    let prints: Vec<_> = memes.iter().map(|(_, x)| Fingerprint::new(x)).collect();
+    let mut parent: Vec<usize> = (0..memes.len()).collect();
+
+    fn root(parent: &mut [usize], mut x: usize) -> usize {
+        while parent[x] != x {
+            parent[x] = parent[parent[x]];
+            x = parent[x];
+        }
+        x
+    }
+
+    for i in 0..prints.len() {
+        for j in i + 1..prints.len() {
+            if prints[i].is_near(&prints[j]) {
+                let (a, b) = (root(&mut parent, i), root(&mut parent, j));
+                parent[b] = a;
+            }
+        }
+    }
+
+    let mut groups: std::collections::BTreeMap<usize, Vec<i32>> = Default::default();
+    for (i, (id, _)) in memes.iter().enumerate() {
+        let r = root(&mut parent, i);
+        groups.entry(r).or_default().push(*id);
+    }
+
+    groups.into_values().filter(|x| x.len() > 1).collect()
+}
+
+#[cfg(test)]
+pub mod test {
+    use super::*;
+
+    #[test]
+    fn normalize_text() {
+        assert_eq!(normalize("  Hello,   World!! "), "hello world");
+    }
+
+    #[test]
+    fn exact_after_normalizing() {
+        let a = Fingerprint::new("I can't believe it's not butter");
+        let b = Fingerprint::new("i cant believe its NOT butter!");
+        assert_eq!(a.similarity(&b), 1.0);
+    }
+
+    #[test]
+    fn near_and_far() {
+        let a = Fingerprint::new("the quick brown fox jumps over the lazy dog");
+        let b = Fingerprint::new("the quick brown fox jumped over the lazy dog");
+        let c = Fingerprint::new("something else entirely");
+        assert!(a.is_near(&b));
+        assert!(!a.is_near(&c));
+    }
+
+    #[test]
+    fn cluster_groups() {
+        let memes = vec![
+            (1, "the quick brown fox jumps over the lazy dog".to_string()),
+            (2, "unrelated".to_string()),
+            (
+                3,
+                "The quick brown fox jumps over the lazy dog!".to_string(),
+            ),
+        ];
+        assert_eq!(clusters(&memes), vec![vec![1, 3]]);
+    }
+}
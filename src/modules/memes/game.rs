@@ -0,0 +1,230 @@
+use rusqlite::Connection;
+use serenity::client::Context;
+use serenity::framework::standard::macros::{command, group};
+use serenity::framework::standard::CommandResult;
+use serenity::futures::stream::StreamExt;
+use serenity::model::channel::Message;
+use serenity::model::id::UserId;
+use serenity::model::misc::Mentionable;
+use serenity::prelude::TypeMapKey;
+use std::collections::HashSet;
+use std::time::Duration;
+
+use super::stats::user_tag;
+use crate::misc::paginate;
+use crate::modules::settings;
+
+/// Channels that currently have a game running in them. Each game is played
+/// out by the command that started it, this only stops a second game from
+/// being started in the same channel.
+pub struct Games;
+
+impl TypeMapKey for Games {
+    type Value = HashSet<u64>;
+}
+
+/// The names a player may use to guess a member: their username, tag and nick.
+async fn answers(ctx: &Context, msg: &Message, user: u64) -> Vec<String> {
+    let mut res = vec![];
+    if let Ok(x) = UserId(user).to_user(ctx).await {
+        res.push(x.name.to_lowercase());
+        res.push(x.tag().to_lowercase());
+    }
+    if let Ok(x) = msg.guild_id.unwrap().member(ctx, user).await {
+        if let Some(nick) = x.nick {
+            res.push(nick.to_lowercase());
+        }
+    }
+    res
+}
+
+fn is_correct(guess: &Message, user: u64, answers: &[String]) -> bool {
+    guess.mentions.iter().any(|x| *x.id.as_u64() == user)
+        || answers.contains(&guess.content.trim().to_lowercase())
+}
+
+async fn play(ctx: &Context, msg: &Message) -> CommandResult {
+    let conn = Connection::open(crate::config::DB_PATH)?;
+    let guild = *msg.guild_id.unwrap().as_u64();
+    let table = super::sql::table(&conn, guild)?;
+    let time = settings::get_number(&conn, guild, "game_time")? as u64;
+    let nsfw = super::nsfw_allowed(ctx, msg).await;
+
+    let meme = match super::sql::random_said_meme(&conn, &table, nsfw) {
+        Ok(x) => x,
+        Err(rusqlite::Error::QueryReturnedNoRows) => {
+            msg.channel_id
+                .say(
+                    &ctx.http,
+                    "no memes have been tagged with who said them yet, see `!saidby`",
+                )
+                .await?;
+            return Ok(());
+        }
+        Err(x) => return Err(x.into()),
+    };
+    let user = meme.said_by.unwrap();
+    let answers = answers(ctx, msg, user).await;
+
+    msg.channel_id
+        .say(
+            &ctx.http,
+            format!(
+                "who said this? you have {} seconds to answer\n>>> {}",
+                time,
+                super::spoiler(&meme, meme.text.clone())
+            ),
+        )
+        .await?;
+
+    let mut replies = msg
+        .channel_id
+        .await_replies(ctx)
+        .timeout(Duration::from_secs(time))
+        .await;
+    while let Some(x) = replies.next().await {
+        if x.author.bot || !is_correct(&x, user, &answers) {
+            continue;
+        }
+
+        let scores = sql::table(&conn, guild)?;
+        let score = sql::add_point(&conn, &scores, *x.author.id.as_u64())?;
+        msg.channel_id
+            .say(
+                &ctx.http,
+                format!(
+                    "{} got it, it was {}! ({} points)",
+                    x.author.mention(),
+                    user_tag(ctx, &user.to_string()).await,
+                    score
+                ),
+            )
+            .await?;
+        return Ok(());
+    }
+
+    msg.channel_id
+        .say(
+            &ctx.http,
+            format!(
+                "time's up! it was {}",
+                user_tag(ctx, &user.to_string()).await
+            ),
+        )
+        .await?;
+    Ok(())
+}
+
+#[command]
+#[only_in("guilds")]
+/// Posts a meme and gives everyone in the channel a limited time to guess who
+/// said it. The first correct guess gets a point.
+async fn start(ctx: &Context, msg: &Message) -> CommandResult {
+    let channel = *msg.channel_id.as_u64();
+    let started = ctx
+        .data
+        .write()
+        .await
+        .entry::<Games>()
+        .or_default()
+        .insert(channel);
+    if !started {
+        msg.channel_id
+            .say(&ctx.http, "a game is already running in this channel")
+            .await?;
+        return Ok(());
+    }
+
+    let res = play(ctx, msg).await;
+    ctx.data
+        .write()
+        .await
+        .entry::<Games>()
+        .or_default()
+        .remove(&channel);
+    res
+}
+
+#[command]
+#[only_in("guilds")]
+/// Shows the guild's `!memegame` leaderboard.
+async fn scores(ctx: &Context, msg: &Message) -> CommandResult {
+    let conn = Connection::open(crate::config::DB_PATH)?;
+    let table = sql::table(&conn, *msg.guild_id.unwrap().as_u64())?;
+
+    let mut lines = vec![];
+    for (i, (user, score)) in sql::all_scores(&conn, &table)?.iter().enumerate() {
+        lines.push(format!(
+            "{}. {} - {}",
+            i + 1,
+            user_tag(ctx, user).await,
+            score
+        ));
+    }
+
+    if lines.is_empty() {
+        msg.channel_id
+            .say(&ctx.http, "nobody has scored any points yet")
+            .await?;
+        return Ok(());
+    }
+    paginate(ctx, msg, "memegame scores:", &lines).await
+}
+
+#[group]
+#[prefix("memegame")]
+#[only_in("guilds")]
+#[default_command(start)]
+#[commands(start, scores)]
+/// Guess who said a meme. Memes have to be tagged with `!saidby` first.
+///
+/// `!memegame` - starts a round in the current channel
+/// `!memegame scores` - shows the leaderboard
+pub struct MemeGame;
+
+mod sql {
+    use rusqlite::{params, Connection, Result};
+
+    pub fn table(conn: &Connection, id: u64) -> Result<String> {
+        let table = format!("x{}_scores", id);
+        conn.execute(
+            &format!(
+                "CREATE TABLE IF NOT EXISTS \"{}\" (
+                    user CHAR(32) UNIQUE,
+                    score INT)",
+                table
+            ),
+            params![],
+        )?;
+
+        Ok(table)
+    }
+
+    /// Gives a user a point, returning their new score.
+    pub fn add_point(conn: &Connection, table: &str, user: u64) -> Result<i64> {
+        conn.execute(
+            &format!(
+                "INSERT INTO \"{}\" (user, score) VALUES (?, 1)
+                 ON CONFLICT(user) DO UPDATE SET score=score+1",
+                table
+            ),
+            params![user.to_string()],
+        )?;
+
+        conn.query_row(
+            &format!("SELECT score FROM \"{}\" WHERE user=?", table),
+            params![user.to_string()],
+            |row| row.get(0),
+        )
+    }
+
+    pub fn all_scores(conn: &Connection, table: &str) -> Result<Vec<(String, i64)>> {
+        let mut stmt = conn.prepare(&format!(
+            "SELECT user, score FROM \"{}\" ORDER BY score DESC",
+            table
+        ))?;
+        let iter = stmt.query_map(params![], |row| Ok((row.get(0)?, row.get(1)?)))?;
+
+        Ok(iter.filter_map(|i| i.ok()).collect())
+    }
+}
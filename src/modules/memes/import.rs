@@ -0,0 +1,149 @@
+use serde::Deserialize;
+
+/// The latest time an imported meme can have, the end of the year 9999.
+const MAX_TIME: i64 = 253_402_300_799;
+
+pub struct Entry {
+    pub text: String,
+    pub time: Option<i64>,
+}
+
+#[derive(Deserialize)]
+#[serde(untagged)]
+enum JsonEntry {
+    Text(String),
+    Full { text: String, time: Option<i64> },
+}
+
+/// Parses an uploaded meme list. The format is picked from the file extension:
+/// `.json` files hold an array of strings or `{"text": ..., "time": ...}`
+/// objects, `.csv` files use the `text` (and optionally `time`) column or the
+/// first column if there is no header, and anything else is read as one meme
+/// per line.
+pub fn parse(filename: &str, data: &[u8]) -> Result<Vec<Entry>, String> {
+    let ext = filename
+        .rsplit('.')
+        .next()
+        .unwrap_or_default()
+        .to_lowercase();
+
+    match ext.as_str() {
+        "json" => parse_json(data),
+        "csv" => parse_csv(data),
+        _ => parse_text(data),
+    }
+}
+
+/// Whether an imported time can be shown as a date.
+pub fn valid_time(time: i64) -> bool {
+    (0..=MAX_TIME).contains(&time)
+}
+
+fn parse_text(data: &[u8]) -> Result<Vec<Entry>, String> {
+    let data = std::str::from_utf8(data).map_err(|_| "file is not valid utf-8")?;
+    Ok(data
+        .lines()
+        .map(|x| Entry {
+            text: x.trim().to_string(),
+            time: None,
+        })
+        .collect())
+}
+
+fn parse_json(data: &[u8]) -> Result<Vec<Entry>, String> {
+    let entries: Vec<JsonEntry> =
+        serde_json::from_slice(data).map_err(|x| format!("invalid json: {}", x))?;
+    Ok(entries
+        .into_iter()
+        .map(|x| match x {
+            JsonEntry::Text(text) => Entry { text, time: None },
+            JsonEntry::Full { text, time } => Entry { text, time },
+        })
+        .collect())
+}
+
+fn parse_csv(data: &[u8]) -> Result<Vec<Entry>, String> {
+    let mut reader = csv::ReaderBuilder::new()
+        .has_headers(false)
+        .flexible(true)
+        .from_reader(data);
+
+    let mut records = reader.records();
+    let mut res = Vec::new();
+    let (mut text_col, mut time_col) = (0, None);
+
+    if let Some(first) = records.next() {
+        let first = first.map_err(|x| format!("invalid csv: {}", x))?;
+        match first.iter().position(|x| x.eq_ignore_ascii_case("text")) {
+            Some(x) => {
+                text_col = x;
+                time_col = first.iter().position(|x| x.eq_ignore_ascii_case("time"));
+            }
+            None => res.push(Entry {
+                text: first.get(0).unwrap_or_default().trim().to_string(),
+                time: None,
+            }),
+        }
+    }
+
+    for record in records {
+        let record = record.map_err(|x| format!("invalid csv: {}", x))?;
+        res.push(Entry {
+            text: record.get(text_col).unwrap_or_default().trim().to_string(),
+            time: time_col
+                .and_then(|x| record.get(x))
+                .and_then(|x| x.trim().parse().ok()),
+        });
+    }
+
+    Ok(res)
+}
+
+#[cfg(test)]
+pub mod test {
+    use super::*;
+
+    fn texts(x: Vec<Entry>) -> Vec<String> {
+        x.into_iter().map(|x| x.text).collect()
+    }
+
+    #[test]
+    fn text_lines() {
+        let res = parse("memes.txt", b"foo\r\n  bar \n\nbaz").unwrap();
+        assert_eq!(texts(res), vec!["foo", "bar", "", "baz"]);
+    }
+
+    #[test]
+    fn json_mixed() {
+        let res = parse("memes.json", br#"["foo", {"text": "bar", "time": 5}]"#).unwrap();
+        assert_eq!(res[1].time, Some(5));
+        assert_eq!(texts(res), vec!["foo", "bar"]);
+    }
+
+    #[test]
+    fn json_invalid() {
+        assert!(parse("memes.json", b"{\"text\": 1}").is_err());
+    }
+
+    #[test]
+    fn csv_header() {
+        let res = parse("memes.CSV", b"id,time,text\n1,5,\"foo, bar\"\n2,,baz").unwrap();
+        assert_eq!(res[0].time, Some(5));
+        assert_eq!(res[1].time, None);
+        assert_eq!(texts(res), vec!["foo, bar", "baz"]);
+    }
+
+    #[test]
+    fn csv_no_header() {
+        let res = parse("memes.csv", b"foo,x\nbar").unwrap();
+        assert_eq!(texts(res), vec!["foo", "bar"]);
+    }
+
+    #[test]
+    fn time_range() {
+        assert!(valid_time(0));
+        assert!(valid_time(1_600_000_000));
+        assert!(!valid_time(-1));
+        assert!(!valid_time(i64::MAX));
+    }
+}
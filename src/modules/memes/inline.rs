@@ -0,0 +1,82 @@
+use lazy_static::lazy_static;
+use regex::Regex;
+use rusqlite::Connection;
+use serenity::client::Context;
+use serenity::framework::standard::CommandResult;
+use serenity::model::channel::Message;
+use std::str::FromStr;
+
+use super::send_meme;
+use crate::modules::settings;
+
+/// The memes referenced in a message as `[[id]]` or `[[name]]`, in order and
+/// without repeats.
+pub fn references(text: &str) -> Vec<String> {
+    lazy_static! {
+        static ref PATTERN: Regex = Regex::new(r"\[\[([^\[\]\s]{1,32})\]\]").unwrap();
+    }
+    let mut res: Vec<String> = vec![];
+    for i in PATTERN.captures_iter(text) {
+        let x = i[1].to_lowercase();
+        if !res.contains(&x) {
+            res.push(x);
+        }
+    }
+    res
+}
+
+/// Answers memes referenced in normal chat, in guilds that have turned on the
+/// `inline_memes` setting. References to memes that don't exist are ignored.
+pub async fn expand(ctx: &Context, msg: &Message) -> CommandResult {
+    let guild = match msg.guild_id {
+        Some(x) => *x.as_u64(),
+        None => return Ok(()),
+    };
+    if msg.author.bot || msg.content.starts_with('!') {
+        return Ok(());
+    }
+    let refs = references(&msg.content);
+    if refs.is_empty() {
+        return Ok(());
+    }
+
+    let conn = Connection::open(crate::config::DB_PATH)?;
+    if !settings::get_bool(&conn, guild, "inline_memes")? {
+        return Ok(());
+    }
+    let max = settings::get_number(&conn, guild, "inline_max")? as usize;
+    let embed = settings::get_bool(&conn, guild, "meme_embed")?;
+    let table = super::sql::table(&conn, guild)?;
+
+    let memes: Vec<_> = refs
+        .iter()
+        .filter_map(|x| match i32::from_str(x) {
+            Ok(id) => super::sql::meme_by_id(&conn, &table, id).ok(),
+            Err(_) => super::sql::meme_by_name(&conn, &table, x).ok(),
+        })
+        .take(max)
+        .collect();
+
+    for i in memes.iter() {
+        send_meme(ctx, msg, i, &[], embed, None).await?;
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+pub mod test {
+    use super::*;
+
+    #[test]
+    fn finds_references() {
+        assert_eq!(
+            references("see [[42]] and [[CopyPasta]] or [[42]] again"),
+            vec!["42", "copypasta"]
+        );
+    }
+
+    #[test]
+    fn ignores_malformed() {
+        assert!(references("[[]] [[two words]] [x] [[[").is_empty());
+    }
+}
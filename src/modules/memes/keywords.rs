@@ -0,0 +1,120 @@
+use rand::seq::SliceRandom;
+use std::collections::HashSet;
+
+use super::Meme;
+
+const STOPWORDS: &[&str] = &[
+    "a", "about", "after", "again", "all", "also", "am", "an", "and", "any", "are", "as", "at",
+    "be", "because", "been", "before", "but", "by", "can", "could", "did", "do", "does", "dont",
+    "for", "from", "get", "got", "had", "has", "have", "he", "her", "him", "his", "how", "http",
+    "https", "i", "if", "im", "in", "into", "is", "it", "its", "just", "like", "me", "more", "my",
+    "no", "not", "now", "of", "on", "one", "only", "or", "our", "out", "she", "so", "some", "than",
+    "that", "the", "their", "them", "then", "there", "they", "this", "to", "too", "up", "us",
+    "very", "was", "we", "were", "what", "when", "where", "which", "who", "why", "will", "with",
+    "would", "www", "you", "your",
+];
+
+fn words(text: &str) -> impl Iterator<Item = String> + '_ {
+    text.split(|x: char| !x.is_alphanumeric() && x != '\'')
+        .map(|x| x.replace('\'', "").to_lowercase())
+        .filter(|x| !x.is_empty())
+}
+
+/// The words in a message worth searching for, without stopwords, numbers
+/// (which are mostly mentions) or repeats.
+pub fn keywords(text: &str) -> Vec<String> {
+    let mut res: Vec<String> = vec![];
+    for i in words(text) {
+        if i.chars().count() < 3
+            || i.chars().all(|x| x.is_numeric())
+            || STOPWORDS.contains(&i.as_str())
+            || res.contains(&i)
+        {
+            continue;
+        }
+        res.push(i);
+    }
+    res
+}
+
+/// The meme that best matches the keywords, where each keyword a meme contains
+/// counts for more the fewer memes contain it. Ties are broken randomly.
+pub fn best<'a>(memes: &[&'a Meme], keywords: &[String]) -> Option<&'a Meme> {
+    let sets: Vec<HashSet<String>> = memes.iter().map(|x| words(&x.text).collect()).collect();
+    let weights: Vec<f64> = keywords
+        .iter()
+        .map(|x| match sets.iter().filter(|y| y.contains(x)).count() {
+            0 => 0.0,
+            n => (memes.len() as f64 / n as f64).ln() + 1.0,
+        })
+        .collect();
+
+    let scores: Vec<f64> = sets
+        .iter()
+        .map(|x| {
+            keywords
+                .iter()
+                .zip(weights.iter())
+                .filter(|(y, _)| x.contains(*y))
+                .map(|(_, w)| w)
+                .sum()
+        })
+        .collect();
+    let top = scores.iter().cloned().fold(0.0, f64::max);
+    if top <= 0.0 {
+        return None;
+    }
+
+    let best: Vec<_> = memes
+        .iter()
+        .zip(scores.iter())
+        .filter(|(_, x)| **x >= top)
+        .map(|(x, _)| *x)
+        .collect();
+    best.choose(&mut rand::thread_rng()).copied()
+}
+
+#[cfg(test)]
+pub mod test {
+    use super::*;
+
+    fn meme(id: i32, text: &str) -> Meme {
+        Meme {
+            id,
+            time: 0,
+            text: text.to_string(),
+            author: None,
+            name: None,
+            said_by: None,
+            locked: false,
+            nsfw: false,
+            spoiler: false,
+            channel: None,
+            source: None,
+            template: false,
+            tags: vec![],
+        }
+    }
+
+    #[test]
+    fn drops_stopwords() {
+        assert_eq!(
+            keywords("I can't believe <@1234> ate the whole Pizza, the pizza!"),
+            vec!["cant", "believe", "ate", "whole", "pizza"]
+        );
+    }
+
+    #[test]
+    fn prefers_rare_words() {
+        let memes = [
+            meme(1, "pizza time"),
+            meme(2, "the cake is a lie"),
+            meme(3, "time flies"),
+            meme(4, "time for bed"),
+        ];
+        let memes: Vec<_> = memes.iter().collect();
+        let words = keywords("is it time for cake yet");
+        assert_eq!(best(&memes, &words).map(|x| x.id), Some(2));
+        assert!(best(&memes, &keywords("nothing matches")).is_none());
+    }
+}
@@ -0,0 +1,240 @@
+use rand::seq::IteratorRandom;
+use rand::Rng;
+use rusqlite::Connection;
+use serenity::client::Context;
+use serenity::framework::standard::macros::command;
+use serenity::framework::standard::Args;
+use serenity::framework::standard::CommandResult;
+use serenity::model::channel::Message;
+use serenity::prelude::TypeMapKey;
+use std::collections::HashMap;
+
+use super::dupes::normalize;
+use crate::modules::settings;
+
+const MAX_WORDS: usize = 100;
+const ATTEMPTS: usize = 20;
+
+/// A word level markov chain built from a guild's memes. Empty strings mark
+/// the start and end of a meme. NSFW memes are left out, as `!markov` works in
+/// any channel.
+pub struct Chain {
+    order: usize,
+    next: HashMap<Vec<String>, HashMap<String, u32>>,
+    memes: HashMap<String, u32>,
+}
+
+impl Chain {
+    pub fn new(order: usize) -> Self {
+        Self {
+            order,
+            next: HashMap::new(),
+            memes: HashMap::new(),
+        }
+    }
+
+    fn transitions(&self, text: &str) -> Vec<(Vec<String>, String)> {
+        let mut words = vec![String::new(); self.order];
+        words.extend(text.split_whitespace().map(|x| x.to_string()));
+        words.push(String::new());
+
+        words
+            .windows(self.order + 1)
+            .map(|x| (x[..self.order].to_vec(), x[self.order].clone()))
+            .collect()
+    }
+
+    pub fn add(&mut self, text: &str) {
+        if text.split_whitespace().next().is_none() {
+            return;
+        }
+        for (state, word) in self.transitions(text) {
+            *self.next.entry(state).or_default().entry(word).or_default() += 1;
+        }
+        *self.memes.entry(normalize(text)).or_default() += 1;
+    }
+
+    pub fn remove(&mut self, text: &str) {
+        if text.split_whitespace().next().is_none() {
+            return;
+        }
+        for (state, word) in self.transitions(text) {
+            if let Some(words) = self.next.get_mut(&state) {
+                if let Some(x) = words.get_mut(&word) {
+                    *x -= 1;
+                    if *x == 0 {
+                        words.remove(&word);
+                    }
+                }
+                if words.is_empty() {
+                    self.next.remove(&state);
+                }
+            }
+        }
+        let key = normalize(text);
+        if let Some(x) = self.memes.get_mut(&key) {
+            *x -= 1;
+            if *x == 0 {
+                self.memes.remove(&key);
+            }
+        }
+    }
+
+    fn pick<R: Rng>(&self, state: &[String], rng: &mut R) -> Option<String> {
+        let words = self.next.get(state)?;
+        let total: u32 = words.values().sum();
+        let mut n = rng.gen_range(0, total);
+        for (word, count) in words {
+            if n < *count {
+                return Some(word.clone());
+            }
+            n -= count;
+        }
+        None
+    }
+
+    fn walk<R: Rng>(&self, seed: Option<&str>, rng: &mut R) -> Option<String> {
+        let mut state = match seed {
+            Some(seed) => self
+                .next
+                .keys()
+                .filter(|x| x[self.order - 1].to_lowercase() == seed.to_lowercase())
+                .choose(rng)?
+                .clone(),
+            None => vec![String::new(); self.order],
+        };
+        // the state holds the words before the seed as well, which the
+        // generated meme shouldn't start with
+        let mut res: Vec<String> = match seed {
+            Some(_) => vec![state[self.order - 1].clone()],
+            None => vec![],
+        };
+
+        while res.len() < MAX_WORDS {
+            let word = self.pick(&state, rng)?;
+            if word.is_empty() {
+                break;
+            }
+            state.remove(0);
+            state.push(word.clone());
+            res.push(word);
+        }
+
+        Some(res.join(" "))
+    }
+
+    /// Generates a new meme, optionally starting from `seed`. Returns `None` if
+    /// nothing could be generated that isn't just a copy of an existing meme.
+    pub fn generate<R: Rng>(&self, seed: Option<&str>, rng: &mut R) -> Option<String> {
+        (0..ATTEMPTS)
+            .filter_map(|_| self.walk(seed, rng))
+            .find(|x| !self.memes.contains_key(&normalize(x)))
+    }
+}
+
+/// Markov chains for each guild, built the first time `!markov` is used and
+/// kept up to date as memes are added and removed.
+pub struct Chains;
+
+impl TypeMapKey for Chains {
+    type Value = HashMap<u64, Chain>;
+}
+
+/// Adds a meme to the guild's chain if one has been built.
+pub async fn added(ctx: &Context, guild: u64, text: &str) {
+    let mut data = ctx.data.write().await;
+    if let Some(x) = data.entry::<Chains>().or_default().get_mut(&guild) {
+        x.add(text);
+    }
+}
+
+/// Removes a meme from the guild's chain if one has been built.
+pub async fn removed(ctx: &Context, guild: u64, text: &str) {
+    let mut data = ctx.data.write().await;
+    if let Some(x) = data.entry::<Chains>().or_default().get_mut(&guild) {
+        x.remove(text);
+    }
+}
+
+#[command]
+#[only_in("guilds")]
+#[usage("[seed word]")]
+/// Makes up a new meme based on the existing ones, optionally starting with the
+/// given word.
+async fn markov(ctx: &Context, msg: &Message, args: Args) -> CommandResult {
+    let conn = Connection::open(crate::config::DB_PATH)?;
+    let guild = *msg.guild_id.unwrap().as_u64();
+    let order = settings::get_number(&conn, guild, "markov_order")? as usize;
+    let seed = args.rest().split_whitespace().next().map(|x| x.to_string());
+
+    let res = {
+        let mut data = ctx.data.write().await;
+        let chains = data.entry::<Chains>().or_default();
+        if chains.get(&guild).map(|x| x.order) != Some(order) {
+            let table = super::sql::table(&conn, guild)?;
+            let mut chain = Chain::new(order);
+            for i in super::sql::all_memes(&conn, &table)?
+                .iter()
+                .filter(|x| !x.nsfw)
+            {
+                chain.add(&i.text);
+            }
+            chains.insert(guild, chain);
+        }
+        chains[&guild].generate(seed.as_deref(), &mut rand::thread_rng())
+    };
+
+    let res = match (res, seed) {
+        (Some(x), _) => x,
+        (None, Some(x)) => format!("couldn't come up with a new meme starting with \"{}\"", x),
+        (None, None) => "couldn't come up with a new meme".to_string(),
+    };
+    msg.channel_id.say(&ctx.http, res).await?;
+    Ok(())
+}
+
+#[cfg(test)]
+pub mod test {
+    use super::*;
+    use rand::rngs::StdRng;
+    use rand::SeedableRng;
+
+    #[test]
+    fn no_verbatim_copies() {
+        let mut chain = Chain::new(1);
+        chain.add("one two three");
+        let mut rng = StdRng::seed_from_u64(0);
+        assert_eq!(chain.generate(None, &mut rng), None);
+    }
+
+    #[test]
+    fn combines_memes() {
+        let mut chain = Chain::new(1);
+        chain.add("the cat sat down");
+        chain.add("my dog sat up");
+        let mut rng = StdRng::seed_from_u64(0);
+        let res = chain.generate(Some("sat"), &mut rng).unwrap();
+        assert!(res == "sat up" || res == "sat down", "{}", res);
+    }
+
+    #[test]
+    fn starts_with_seed() {
+        let mut chain = Chain::new(2);
+        chain.add("the cat sat down");
+        chain.add("my dog sat up");
+        let mut rng = StdRng::seed_from_u64(0);
+        let res = chain.generate(Some("sat"), &mut rng).unwrap();
+        assert!(res == "sat up" || res == "sat down", "{}", res);
+    }
+
+    #[test]
+    fn remove_undoes_add() {
+        let mut chain = Chain::new(2);
+        chain.add("a b c");
+        chain.add("a b d");
+        chain.remove("a b d");
+        chain.remove("a b c");
+        assert!(chain.next.is_empty());
+        assert!(chain.memes.is_empty());
+    }
+}
@@ -0,0 +1,86 @@
+use chrono::{Datelike, TimeZone, Utc};
+use chrono_tz::Tz;
+use rusqlite::Connection;
+use serenity::client::Context;
+use serenity::framework::standard::CommandResult;
+use serenity::model::channel::Message;
+use serenity::model::id::ChannelId;
+
+use super::{channel_nsfw, listing, nsfw_allowed, Meme};
+use crate::misc::{pages, paginate};
+use crate::modules::schedule::{self, Period};
+use crate::modules::settings;
+
+/// Memes added on today's date in previous years, oldest first.
+fn memes_on_this_day(conn: &Connection, guild: u64) -> rusqlite::Result<(Tz, Vec<Meme>)> {
+    let table = super::sql::table(conn, guild)?;
+    let tz = settings::get_timezone(conn, guild)?;
+    let today = Utc::now().with_timezone(&tz).date();
+
+    let mut res: Vec<_> = super::sql::all_memes(conn, &table)?
+        .into_iter()
+        .filter(|x| match tz.timestamp_opt(x.time, 0).single() {
+            Some(date) => {
+                x.time != 0
+                    && date.year() < today.year()
+                    && date.month() == today.month()
+                    && date.day() == today.day()
+            }
+            None => false,
+        })
+        .collect();
+    res.sort_by_key(|x| x.time);
+    Ok((tz, res))
+}
+
+fn lines(tz: &Tz, memes: &[Meme], nsfw: bool) -> Vec<String> {
+    memes
+        .iter()
+        .map(|x| {
+            let year = tz.timestamp_opt(x.time, 0).single().map_or(0, |x| x.year());
+            format!("[{}] {} {}", year, x.id, listing(x, nsfw))
+        })
+        .collect()
+}
+
+/// Answers `!meme onthisday`.
+pub async fn reply(ctx: &Context, msg: &Message) -> CommandResult {
+    let conn = Connection::open(crate::config::DB_PATH)?;
+    let (tz, memes) = memes_on_this_day(&conn, *msg.guild_id.unwrap().as_u64())?;
+
+    if memes.is_empty() {
+        msg.channel_id
+            .say(
+                &ctx.http,
+                "no memes were added on this day in previous years",
+            )
+            .await?;
+        return Ok(());
+    }
+    let nsfw = nsfw_allowed(ctx, msg).await;
+    paginate(ctx, msg, "on this day:", &lines(&tz, &memes, nsfw)).await
+}
+
+/// Posts the day's anniversaries to the guild's `onthisday_channel`, if it has
+/// one and the post is due.
+pub async fn scheduled(ctx: &Context, guild: u64) -> CommandResult {
+    let conn = Connection::open(crate::config::DB_PATH)?;
+    let channel = match settings::get_channel(&conn, guild, "onthisday_channel")? {
+        Some(x) => x,
+        None => return Ok(()),
+    };
+    let time = match schedule::due(&conn, guild, "onthisday", Period::Daily)? {
+        Some(x) => x,
+        None => return Ok(()),
+    };
+
+    let (tz, memes) = memes_on_this_day(&conn, guild)?;
+    if !memes.is_empty() {
+        let nsfw = channel_nsfw(ctx, ChannelId(channel)).await;
+        for i in pages("on this day:", &lines(&tz, &memes, nsfw)) {
+            ChannelId(channel).say(&ctx.http, i).await?;
+        }
+    }
+    schedule::mark_done(&conn, guild, "onthisday", &time)?;
+    Ok(())
+}
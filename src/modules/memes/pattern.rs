@@ -0,0 +1,77 @@
+use rand::seq::IteratorRandom;
+use regex::{Regex, RegexBuilder};
+use std::time::{Duration, Instant};
+
+use super::Meme;
+
+const MAX_LENGTH: usize = 200;
+const SIZE_LIMIT: usize = 1 << 20;
+const TIMEOUT: Duration = Duration::from_secs(2);
+
+/// The pattern in a `/pattern/` search, if `arg` is one.
+pub fn parse(arg: &str) -> Option<&str> {
+    if arg.len() > 2 && arg.starts_with('/') && arg.ends_with('/') {
+        Some(&arg[1..arg.len() - 1])
+    } else {
+        None
+    }
+}
+
+/// Compiles a case insensitive pattern, refusing ones that are too long or
+/// would compile to something too large to run quickly.
+pub fn compile(pattern: &str) -> Result<Regex, String> {
+    if pattern.chars().count() > MAX_LENGTH {
+        return Err(format!(
+            "patterns can't be longer than {} characters",
+            MAX_LENGTH
+        ));
+    }
+    RegexBuilder::new(pattern)
+        .case_insensitive(true)
+        .size_limit(SIZE_LIMIT)
+        .dfa_size_limit(SIZE_LIMIT)
+        .build()
+        .map_err(|x| match x {
+            regex::Error::CompiledTooBig(_) => "pattern is too complex".to_string(),
+            x => format!("invalid pattern: {}", x),
+        })
+}
+
+/// Picks a random meme whose text matches `pattern`. The memes are searched
+/// on a blocking thread, giving up once the search has taken too long.
+pub async fn search(memes: Vec<Meme>, pattern: Regex) -> Result<Option<Meme>, String> {
+    tokio::task::spawn_blocking(move || {
+        let start = Instant::now();
+        let mut found = vec![];
+        for i in memes {
+            if start.elapsed() > TIMEOUT {
+                return Err("search took too long, try a simpler pattern".to_string());
+            }
+            if pattern.is_match(&i.text) {
+                found.push(i);
+            }
+        }
+        Ok(found.into_iter().choose(&mut rand::thread_rng()))
+    })
+    .await
+    .map_err(|x| x.to_string())?
+}
+
+#[cfg(test)]
+pub mod test {
+    use super::*;
+
+    #[test]
+    fn parses_slashes() {
+        assert_eq!(parse("/ab+c/"), Some("ab+c"));
+        assert_eq!(parse("//"), None);
+        assert_eq!(parse("/abc"), None);
+    }
+
+    #[test]
+    fn refuses_large_patterns() {
+        assert!(compile("(?i)copy ?pasta").is_ok());
+        assert!(compile(&"a".repeat(MAX_LENGTH + 1)).is_err());
+        assert!(compile(r"\w{1000}\w{1000}").is_err());
+    }
+}
@@ -0,0 +1,160 @@
+use rusqlite::Connection;
+use serenity::client::Context;
+use serenity::framework::standard::macros::command;
+use serenity::framework::standard::Args;
+use serenity::framework::standard::CommandResult;
+use serenity::model::channel::{Channel, Message};
+use serenity::model::event::ChannelPinsUpdateEvent;
+use serenity::model::id::ChannelId;
+use std::collections::HashSet;
+
+use super::{dupes, markov, policy, quota, validate, EDIT_MEMES_CHECK_CHECK};
+use crate::modules::settings;
+
+/// Adds pinned messages as memes said by whoever sent them, skipping ones that
+/// were already added or that the policy rejects. `author` is who is adding
+/// them, if anyone, and `allowance` how many more memes their quota lets them
+/// add. Returns the text of the added memes, how many pins were skipped and
+/// how many weren't added because of the quota.
+fn add_pins(
+    conn: &mut Connection,
+    guild: u64,
+    pins: &[Message],
+    author: Option<u64>,
+    policy: Option<&policy::Policy>,
+    mut allowance: Option<i64>,
+) -> rusqlite::Result<(Vec<String>, usize, usize)> {
+    let table = super::sql::table(conn, guild)?;
+    let tx = conn.transaction()?;
+    let mut seen: HashSet<String> = super::sql::all_memes(&tx, &table)?
+        .into_iter()
+        .map(|x| dupes::normalize(&x.text))
+        .collect();
+    let (mut added, mut skipped, mut over) = (vec![], 0, 0);
+
+    for i in pins {
+        let source = i.link();
+        if validate(&i.content, policy).is_err()
+            || super::sql::meme_by_source(&tx, &table, &source).is_ok()
+            || !seen.insert(dupes::normalize(&i.content))
+        {
+            skipped += 1;
+            continue;
+        }
+        if allowance == Some(0) {
+            over += 1;
+            continue;
+        }
+
+        let said_by = *i.author.id.as_u64();
+        let time = i.timestamp.timestamp();
+        super::sql::add_meme(
+            &tx,
+            &table,
+            time,
+            &i.content,
+            author.unwrap_or(said_by),
+            None,
+        )?;
+        let id = super::sql::get_seq(&tx, &table)?;
+        super::sql::set_said_by(&tx, &table, id, Some(said_by))?;
+        super::sql::set_source(&tx, &table, id, &source)?;
+        if let Some(x) = author {
+            quota::record(&tx, guild, x, quota::Action::Add)?;
+        }
+        allowance = allowance.map(|x| x - 1);
+        added.push(i.content.clone());
+    }
+    tx.commit()?;
+
+    Ok((added, skipped, over))
+}
+
+#[command("importpins")]
+#[num_args(1)]
+#[only_in("guilds")]
+#[checks(edit_memes_check)]
+#[usage("<channel>")]
+/// Adds every message pinned in a channel as a meme, linking back to the
+/// original message. Pins that were already added are skipped.
+async fn import_pins(ctx: &Context, msg: &Message, args: Args) -> CommandResult {
+    let guild = msg.guild_id.unwrap();
+    let arg = args.rest().trim();
+    let channel = serenity::utils::parse_channel(arg).or_else(|| arg.parse().ok());
+
+    let channel = match channel {
+        Some(x) => ChannelId(x).to_channel_cached(ctx).await,
+        None => None,
+    };
+    let channel = match channel {
+        Some(Channel::Guild(x)) if x.guild_id == guild => x.id,
+        _ => {
+            msg.channel_id
+                .say(&ctx.http, format!("channel {} not found", arg))
+                .await?;
+            return Ok(());
+        }
+    };
+
+    let pins = channel.pins(&ctx.http).await?;
+    let policy = policy::for_invoker(ctx, msg).await?;
+    let allowance = quota::allowance(ctx, msg, quota::Action::Add).await?;
+    let mut conn = Connection::open(crate::config::DB_PATH)?;
+    let (added, skipped, over) = add_pins(
+        &mut conn,
+        *guild.as_u64(),
+        &pins,
+        Some(*msg.author.id.as_u64()),
+        policy.as_ref(),
+        allowance,
+    )?;
+
+    for i in added.iter() {
+        markov::added(ctx, *guild.as_u64(), i).await;
+    }
+
+    let mut res = format!(
+        "imported {} pins ({} already added or not allowed)",
+        added.len(),
+        skipped
+    );
+    if over > 0 {
+        res.push_str(&format!(
+            "\n{} pins weren't added as you've reached your addmeme quota",
+            over
+        ));
+    }
+    msg.channel_id.say(&ctx.http, res).await?;
+    Ok(())
+}
+
+/// Adds a channel's newest pin as a meme in guilds that have turned on the
+/// `capture_pins` setting.
+pub async fn pinned(ctx: &Context, event: &ChannelPinsUpdateEvent) -> CommandResult {
+    let guild = match event.guild_id {
+        Some(x) => *x.as_u64(),
+        None => return Ok(()),
+    };
+    let mut conn = Connection::open(crate::config::DB_PATH)?;
+    if !settings::get_bool(&conn, guild, "capture_pins")? {
+        return Ok(());
+    }
+
+    // unpinning sends this event as well, in which case the newest pin has
+    // usually been added already
+    let pins = event.channel_id.pins(&ctx.http).await?;
+    let policy = policy::Policy::load(&conn, guild)?;
+    let (added, _, _) = add_pins(
+        &mut conn,
+        guild,
+        &pins[..pins.len().min(1)],
+        None,
+        Some(&policy),
+        None,
+    )?;
+
+    for i in added.iter() {
+        markov::added(ctx, guild, i).await;
+    }
+    Ok(())
+}
@@ -0,0 +1,279 @@
+use lazy_static::lazy_static;
+use regex::Regex;
+use rusqlite::Connection;
+use serenity::client::Context;
+use serenity::framework::standard::macros::{check, command, group};
+use serenity::framework::standard::Args;
+use serenity::framework::standard::CommandResult;
+use serenity::framework::standard::Reason;
+use serenity::model::channel::Message;
+
+use crate::misc::paginate;
+use crate::modules::settings;
+
+const KINDS: &[&str] = &["word", "regex", "domain"];
+
+/// A guild's rules for what memes may contain. Members with the `p` flag are
+/// exempt from them.
+pub struct Policy {
+    min_length: usize,
+    max_length: usize,
+    patterns: Vec<(String, Regex)>,
+    domains: Vec<String>,
+}
+
+fn pattern(kind: &str, value: &str) -> Option<Regex> {
+    match kind {
+        "word" => Regex::new(&format!(r"(?i)\b{}\b", regex::escape(value))).ok(),
+        "regex" => Regex::new(value).ok(),
+        _ => None,
+    }
+}
+
+fn hosts(text: &str) -> Vec<String> {
+    lazy_static! {
+        static ref URL: Regex = Regex::new(r"(?i)\bhttps?://([^/\s:?#]+)").unwrap();
+    }
+    URL.captures_iter(text)
+        .map(|x| x[1].trim_end_matches('.').to_lowercase())
+        .collect()
+}
+
+impl Policy {
+    pub fn load(conn: &Connection, guild: u64) -> rusqlite::Result<Self> {
+        let table = sql::table(conn, guild)?;
+        let mut res = Self {
+            min_length: settings::get_number(conn, guild, "min_length")? as usize,
+            max_length: settings::get_number(conn, guild, "max_length")? as usize,
+            patterns: vec![],
+            domains: vec![],
+        };
+
+        for (_, kind, value) in sql::all_rules(conn, &table)? {
+            if kind == "domain" {
+                res.domains.push(value);
+            } else if let Some(x) = pattern(&kind, &value) {
+                res.patterns.push((value, x));
+            }
+        }
+
+        Ok(res)
+    }
+
+    /// Checks a meme against the policy, returning why it was rejected.
+    pub fn check(&self, text: &str) -> Result<(), String> {
+        let len = text.chars().count();
+        if len < self.min_length {
+            return Err(format!(
+                "memes must be at least {} characters long",
+                self.min_length
+            ));
+        }
+        if len > self.max_length {
+            return Err(format!(
+                "memes can be at most {} characters long (this one is {})",
+                self.max_length, len
+            ));
+        }
+        if let Some((x, _)) = self.patterns.iter().find(|(_, x)| x.is_match(text)) {
+            return Err(format!("memes can't contain `{}`", x));
+        }
+        for host in hosts(text) {
+            if let Some(x) = self
+                .domains
+                .iter()
+                .find(|x| host == **x || host.ends_with(&format!(".{}", x)))
+            {
+                return Err(format!("links to {} aren't allowed", x));
+            }
+        }
+
+        Ok(())
+    }
+}
+
+/// Loads the guild's policy for the invoker, or `None` if they are exempt.
+pub async fn for_invoker(ctx: &Context, msg: &Message) -> rusqlite::Result<Option<Policy>> {
+    if crate::modules::perms::check_perms(ctx, msg, "p")
+        .await
+        .is_ok()
+    {
+        return Ok(None);
+    }
+    let conn = Connection::open(crate::config::DB_PATH)?;
+    Policy::load(&conn, *msg.guild_id.unwrap().as_u64()).map(Some)
+}
+
+#[check]
+#[display_in_help(true)]
+async fn policy_flag_p(ctx: &Context, msg: &Message) -> Result<(), Reason> {
+    crate::modules::perms::check_perms(ctx, msg, "p").await
+}
+
+#[command]
+#[aliases(ls)]
+#[only_in("guilds")]
+/// Lists the guild's banned words, patterns and link domains.
+async fn list(ctx: &Context, msg: &Message) -> CommandResult {
+    let conn = Connection::open(crate::config::DB_PATH)?;
+    let guild = *msg.guild_id.unwrap().as_u64();
+    let table = sql::table(&conn, guild)?;
+
+    let mut lines = vec![format!(
+        "length: {} to {} characters",
+        settings::get_number(&conn, guild, "min_length")?,
+        settings::get_number(&conn, guild, "max_length")?
+    )];
+    for (id, kind, value) in sql::all_rules(&conn, &table)? {
+        lines.push(format!("{} {} {}", id, kind, value));
+    }
+    paginate(ctx, msg, "", &lines).await
+}
+
+#[command]
+#[min_args(2)]
+#[only_in("guilds")]
+#[usage("<word|regex|domain> <value>")]
+/// Bans a word, a regular expression or links to a domain (and its
+/// subdomains) from memes.
+async fn add(ctx: &Context, msg: &Message, mut args: Args) -> CommandResult {
+    let conn = Connection::open(crate::config::DB_PATH)?;
+    let table = sql::table(&conn, *msg.guild_id.unwrap().as_u64())?;
+    let kind = args.single::<String>()?.to_lowercase();
+    let value = args.rest().trim().to_string();
+
+    let res = if !KINDS.contains(&kind.as_str()) {
+        format!("`{}` is not one of {}", kind, KINDS.join(", "))
+    } else if kind == "regex" && Regex::new(&value).is_err() {
+        format!("`{}` is not a valid regular expression", value)
+    } else {
+        let value = if kind == "domain" {
+            value.trim_start_matches("*.").to_lowercase()
+        } else {
+            value
+        };
+        sql::add_rule(&conn, &table, &kind, &value)?;
+        format!("banned {} `{}`", kind, value)
+    };
+
+    msg.channel_id.say(&ctx.http, res).await?;
+    Ok(())
+}
+
+#[command]
+#[aliases(rm, remove, delete)]
+#[num_args(1)]
+#[only_in("guilds")]
+#[usage("<id>")]
+/// Removes a rule from the policy.
+async fn del(ctx: &Context, msg: &Message, mut args: Args) -> CommandResult {
+    let conn = Connection::open(crate::config::DB_PATH)?;
+    let table = sql::table(&conn, *msg.guild_id.unwrap().as_u64())?;
+    let id: i32 = args.single()?;
+
+    let res = if sql::del_rule(&conn, &table, id)? {
+        format!("rule {} removed", id)
+    } else {
+        format!("rule {} not found", id)
+    };
+
+    msg.channel_id.say(&ctx.http, res).await?;
+    Ok(())
+}
+
+#[group]
+#[prefix("memepolicy")]
+#[only_in("guilds")]
+#[commands(list, add, del)]
+#[checks(policy_flag_p)]
+/// Controls what memes may contain. The policy applies to `!addmeme`,
+/// `!editmeme`, `!submitmeme` and `!memes import` for everyone without the `p`
+/// flag. Lengths are set with the `min_length` and `max_length` settings.
+pub struct MemePolicy;
+
+mod sql {
+    use rusqlite::{params, Connection, Result};
+
+    pub fn table(conn: &Connection, id: u64) -> Result<String> {
+        let table = format!("x{}_policy", id);
+        conn.execute(
+            &format!(
+                "CREATE TABLE IF NOT EXISTS \"{}\" (
+                    id INTEGER PRIMARY KEY AUTOINCREMENT,
+                    kind CHAR(16),
+                    value TEXT)",
+                table
+            ),
+            params![],
+        )?;
+
+        Ok(table)
+    }
+
+    pub fn all_rules(conn: &Connection, table: &str) -> Result<Vec<(i32, String, String)>> {
+        let mut stmt = conn.prepare(&format!(
+            "SELECT id, kind, value FROM \"{}\" ORDER BY id",
+            table
+        ))?;
+        let iter = stmt.query_map(params![], |row| Ok((row.get(0)?, row.get(1)?, row.get(2)?)))?;
+
+        Ok(iter.filter_map(|i| i.ok()).collect())
+    }
+
+    pub fn add_rule(conn: &Connection, table: &str, kind: &str, value: &str) -> Result<()> {
+        conn.execute(
+            &format!("INSERT INTO \"{}\" (kind, value) VALUES (?, ?)", table),
+            params![kind, value],
+        )?;
+
+        Ok(())
+    }
+
+    /// Removes a rule, returning whether it existed.
+    pub fn del_rule(conn: &Connection, table: &str, id: i32) -> Result<bool> {
+        let n = conn.execute(
+            &format!("DELETE FROM \"{}\" WHERE id=?", table),
+            params![id],
+        )?;
+
+        Ok(n > 0)
+    }
+}
+
+#[cfg(test)]
+pub mod test {
+    use super::*;
+
+    fn policy() -> Policy {
+        Policy {
+            min_length: 3,
+            max_length: 40,
+            patterns: vec![
+                ("heck".into(), pattern("word", "heck").unwrap()),
+                ("[0-9]{4}".into(), pattern("regex", "[0-9]{4}").unwrap()),
+            ],
+            domains: vec!["example.com".into()],
+        }
+    }
+
+    #[test]
+    fn length() {
+        assert!(policy().check("ab").is_err());
+        assert!(policy().check("abc").is_ok());
+        assert!(policy().check(&"a".repeat(41)).is_err());
+    }
+
+    #[test]
+    fn words() {
+        assert!(policy().check("what the HECK").is_err());
+        assert!(policy().check("checking").is_ok());
+        assert!(policy().check("pin 1234").is_err());
+    }
+
+    #[test]
+    fn domains() {
+        assert!(policy().check("http://example.com/x").is_err());
+        assert!(policy().check("https://www.Example.com").is_err());
+        assert!(policy().check("http://notexample.com").is_ok());
+    }
+}
@@ -0,0 +1,254 @@
+use rusqlite::Connection;
+use serenity::client::Context;
+use serenity::framework::standard::macros::{command, group};
+use serenity::framework::standard::Args;
+use serenity::framework::standard::CommandResult;
+use serenity::model::channel::Message;
+use serenity::model::id::{ChannelId, MessageId, UserId};
+use std::str::FromStr;
+
+use super::{find_duplicate, now, preview, Duplicate, EDIT_MEMES_CHECK_CHECK};
+use crate::misc::paginate;
+
+pub struct Submission {
+    pub id: i32,
+    pub time: i64,
+    pub text: String,
+    pub author: u64,
+    pub channel: u64,
+    pub message: u64,
+}
+
+/// Tells the submitter what happened to their meme, falling back to replying
+/// to the submission if their DMs are closed. Failing to tell them isn't an
+/// error, as the submission has already been dealt with by then.
+async fn notify(ctx: &Context, sub: &Submission, text: &str) {
+    let dm = match UserId(sub.author).create_dm_channel(ctx).await {
+        Ok(x) => x.say(&ctx.http, text).await.is_ok(),
+        Err(_) => false,
+    };
+
+    if !dm {
+        let res = ChannelId(sub.channel)
+            .send_message(&ctx.http, |m| {
+                m.content(format!("<@{}> {}", sub.author, text));
+                m.reference_message((ChannelId(sub.channel), MessageId(sub.message)))
+            })
+            .await;
+        if let Err(x) = res {
+            println!(
+                "error notifying {} about submission {}: {}",
+                sub.author, sub.id, x
+            );
+        }
+    }
+}
+
+#[command]
+#[aliases(ls)]
+#[only_in("guilds")]
+/// Lists all memes waiting for review.
+async fn list(ctx: &Context, msg: &Message) -> CommandResult {
+    let conn = Connection::open(crate::config::DB_PATH)?;
+    let table = sql::table(&conn, *msg.guild_id.unwrap().as_u64())?;
+    let subs = sql::all_submissions(&conn, &table)?;
+
+    if subs.is_empty() {
+        msg.channel_id.say(&ctx.http, "the queue is empty").await?;
+        return Ok(());
+    }
+
+    let mut lines = vec![];
+    for i in subs.iter() {
+        let author = match ctx.cache.user(i.author).await {
+            Some(x) => x.tag(),
+            None => i.author.to_string(),
+        };
+        lines.push(format!("{} [{}] {}", i.id, author, preview(&i.text)));
+    }
+    paginate(ctx, msg, "", &lines).await
+}
+
+#[command]
+#[num_args(1)]
+#[only_in("guilds")]
+#[usage("<id>")]
+/// Adds a queued meme to the list, unless the same meme has been added since
+/// it was submitted.
+async fn approve(ctx: &Context, msg: &Message, args: Args) -> CommandResult {
+    let mut conn = Connection::open(crate::config::DB_PATH)?;
+    let guild = *msg.guild_id.unwrap().as_u64();
+    let table = sql::table(&conn, guild)?;
+    let memes = super::sql::table(&conn, guild)?;
+    let arg = i32::from_str(args.rest())?;
+
+    // this needs to be in its own scope as tx is not compatable with .await
+    let res = {
+        let tx = conn.transaction()?;
+        let res = match sql::submission_by_id(&tx, &table, arg) {
+            Ok(x) => match find_duplicate(&tx, &memes, &x.text)? {
+                Duplicate::Exact(id) => Err(format!(
+                    "submission {} already exists as meme {}, reject it instead",
+                    arg, id
+                )),
+                _ => {
+                    super::sql::add_meme(&tx, &memes, now(), &x.text, x.author, None)?;
+                    sql::del_submission(&tx, &table, arg)?;
+                    let id = super::sql::get_seq(&tx, &memes)?;
+                    Ok((x, id))
+                }
+            },
+            Err(_) => Err(format!("submission {} not found", arg)),
+        };
+        tx.commit()?;
+        res
+    };
+
+    match res {
+        Ok((sub, id)) => {
+            super::markov::added(ctx, guild, &sub.text).await;
+            notify(
+                ctx,
+                &sub,
+                &format!("your meme was approved and added as meme {}", id),
+            )
+            .await;
+            msg.channel_id
+                .say(
+                    &ctx.http,
+                    format!("submission {} added as meme {}", arg, id),
+                )
+                .await?;
+        }
+        Err(x) => drop(msg.channel_id.say(&ctx.http, x).await?),
+    }
+    Ok(())
+}
+
+#[command]
+#[min_args(1)]
+#[only_in("guilds")]
+#[usage("<id> [reason]")]
+/// Removes a meme from the queue without adding it.
+async fn reject(ctx: &Context, msg: &Message, mut args: Args) -> CommandResult {
+    let conn = Connection::open(crate::config::DB_PATH)?;
+    let table = sql::table(&conn, *msg.guild_id.unwrap().as_u64())?;
+    let arg: i32 = args.single()?;
+    let reason = args.rest().trim().to_string();
+
+    let sub = match sql::submission_by_id(&conn, &table, arg) {
+        Ok(x) => {
+            sql::del_submission(&conn, &table, arg)?;
+            x
+        }
+        Err(_) => {
+            msg.channel_id
+                .say(&ctx.http, format!("submission {} not found", arg))
+                .await?;
+            return Ok(());
+        }
+    };
+
+    let mut text = format!("your meme was rejected: {}", preview(&sub.text));
+    if !reason.is_empty() {
+        text.push_str(&format!("\nreason: {}", reason));
+    }
+    notify(ctx, &sub, &text).await;
+    msg.channel_id
+        .say(&ctx.http, format!("submission {} rejected", arg))
+        .await?;
+    Ok(())
+}
+
+#[group]
+#[prefix("queue")]
+#[only_in("guilds")]
+#[commands(list, approve, reject)]
+#[checks(edit_memes_check)]
+/// The queue group contains commands for reviewing memes sent in with
+/// `!submitmeme`.
+///
+/// All commands in this group require the `m` permission flag.
+///
+/// `!queue list` - lists memes waiting for review
+/// `!queue approve <id>` - adds a queued meme to the list
+/// `!queue reject <id> [reason]` - discards a queued meme
+pub struct Queue;
+
+pub mod sql {
+    use super::*;
+    use rusqlite::{params, Connection, Result, Row};
+
+    pub fn table(conn: &Connection, id: u64) -> Result<String> {
+        let table = format!("x{}_queue", id);
+        conn.execute(
+            &format!(
+                "CREATE TABLE IF NOT EXISTS \"{}\" (
+                    id INTEGER PRIMARY KEY AUTOINCREMENT,
+                    time INT,
+                    text VARCHAR(500),
+                    author CHAR(32),
+                    channel CHAR(32),
+                    message CHAR(32))",
+                table
+            ),
+            params![],
+        )?;
+
+        Ok(table)
+    }
+
+    fn submission_from_row(row: &Row) -> Result<Submission> {
+        Ok(Submission {
+            id: row.get(0)?,
+            time: row.get(1)?,
+            text: row.get(2)?,
+            author: u64::from_str(&row.get::<usize, String>(3)?).unwrap(),
+            channel: u64::from_str(&row.get::<usize, String>(4)?).unwrap(),
+            message: u64::from_str(&row.get::<usize, String>(5)?).unwrap(),
+        })
+    }
+
+    pub fn all_submissions(conn: &Connection, table: &str) -> Result<Vec<Submission>> {
+        let mut stmt = conn.prepare(&format!("SELECT * FROM \"{}\" ORDER BY id", table))?;
+        let iter = stmt.query_map(params![], submission_from_row)?;
+
+        Ok(iter.filter_map(|i| i.ok()).collect())
+    }
+
+    pub fn submission_by_id(conn: &Connection, table: &str, id: i32) -> Result<Submission> {
+        conn.query_row(
+            &format!("SELECT * FROM \"{}\" WHERE id=?", table),
+            params![id],
+            submission_from_row,
+        )
+    }
+
+    pub fn add_submission(conn: &Connection, table: &str, sub: &Submission) -> Result<i32> {
+        conn.execute(
+            &format!(
+                "INSERT INTO \"{}\" (time, text, author, channel, message)
+                 VALUES (?, ?, ?, ?, ?)",
+                table
+            ),
+            params![
+                sub.time,
+                sub.text,
+                sub.author.to_string(),
+                sub.channel.to_string(),
+                sub.message.to_string()
+            ],
+        )?;
+
+        Ok(conn.last_insert_rowid() as i32)
+    }
+
+    pub fn del_submission(conn: &Connection, table: &str, id: i32) -> Result<()> {
+        conn.execute(
+            &format!("DELETE FROM \"{}\" WHERE id=?", table),
+            params![id],
+        )?;
+
+        Ok(())
+    }
+}
@@ -0,0 +1,202 @@
+use chrono::{DateTime, Utc};
+use chrono_tz::Tz;
+use rusqlite::Connection;
+use serenity::client::Context;
+use serenity::framework::standard::macros::command;
+use serenity::framework::standard::CommandResult;
+use serenity::model::channel::Message;
+
+use super::now;
+use crate::modules::settings;
+
+#[derive(Clone, Copy)]
+pub enum Action {
+    Add,
+    Submit,
+}
+
+impl Action {
+    fn key(&self) -> &'static str {
+        match self {
+            Action::Add => "addmeme",
+            Action::Submit => "submitmeme",
+        }
+    }
+
+    fn setting(&self) -> &'static str {
+        match self {
+            Action::Add => "addmeme_quota",
+            Action::Submit => "submit_quota",
+        }
+    }
+}
+
+/// The start and end of the guild's current quota period. Quotas reset at
+/// midnight in the guild's timezone, either every day or once a week.
+fn period(conn: &Connection, guild: u64) -> rusqlite::Result<(DateTime<Tz>, DateTime<Tz>)> {
+    let tz = settings::get_timezone(conn, guild)?;
+    let period = settings::get_period(conn, guild, "quota_period")?;
+    let start = period.last(&Utc::now().with_timezone(&tz), 0);
+    Ok((start, start + period.length()))
+}
+
+/// How many more times a member can do something this period, or `None` if
+/// the guild doesn't limit it.
+pub fn remaining(
+    conn: &Connection,
+    guild: u64,
+    user: u64,
+    action: Action,
+) -> rusqlite::Result<Option<i64>> {
+    let quota = settings::get_number(conn, guild, action.setting())?;
+    if quota == 0 {
+        return Ok(None);
+    }
+    let table = sql::table(conn, guild)?;
+    let (start, _) = period(conn, guild)?;
+    let used = sql::count(conn, &table, user, action.key(), start.timestamp())?;
+    Ok(Some((quota - used).max(0)))
+}
+
+/// How many more times the invoker can do something this period, or `None` if
+/// they aren't limited. Members with the `p` flag have no quotas.
+pub async fn allowance(
+    ctx: &Context,
+    msg: &Message,
+    action: Action,
+) -> rusqlite::Result<Option<i64>> {
+    if crate::modules::perms::check_perms(ctx, msg, "p")
+        .await
+        .is_ok()
+    {
+        return Ok(None);
+    }
+    let conn = Connection::open(crate::config::DB_PATH)?;
+    let guild = *msg.guild_id.unwrap().as_u64();
+    remaining(&conn, guild, *msg.author.id.as_u64(), action)
+}
+
+/// Whether the invoker's quota is used up, returning what to tell them if it
+/// is.
+pub async fn exceeded(
+    ctx: &Context,
+    msg: &Message,
+    action: Action,
+) -> rusqlite::Result<Option<String>> {
+    let conn = Connection::open(crate::config::DB_PATH)?;
+    let guild = *msg.guild_id.unwrap().as_u64();
+    match allowance(ctx, msg, action).await? {
+        Some(0) => {
+            let (_, end) = period(&conn, guild)?;
+            Ok(Some(format!(
+                "you've reached your {} quota, it resets at {}",
+                action.key(),
+                end.format("%Y-%m-%d %H:%M %Z")
+            )))
+        }
+        _ => Ok(None),
+    }
+}
+
+/// Counts something a member did against their quota.
+pub fn record(conn: &Connection, guild: u64, user: u64, action: Action) -> rusqlite::Result<()> {
+    let table = sql::table(conn, guild)?;
+    sql::add(conn, &table, user, action.key(), now())
+}
+
+#[command("quota")]
+#[only_in("guilds")]
+/// Shows how many more memes you can add and submit before your quota resets.
+async fn show_quota(ctx: &Context, msg: &Message) -> CommandResult {
+    if crate::modules::perms::check_perms(ctx, msg, "p")
+        .await
+        .is_ok()
+    {
+        msg.channel_id
+            .say(&ctx.http, "you have the p flag, quotas don't apply to you")
+            .await?;
+        return Ok(());
+    }
+
+    let conn = Connection::open(crate::config::DB_PATH)?;
+    let guild = *msg.guild_id.unwrap().as_u64();
+    let user = *msg.author.id.as_u64();
+    let mut lines = vec![];
+    for i in [Action::Add, Action::Submit].iter() {
+        lines.push(match remaining(&conn, guild, user, *i)? {
+            Some(x) => format!("{}: {} left", i.key(), x),
+            None => format!("{}: no limit", i.key()),
+        });
+    }
+    let (_, end) = period(&conn, guild)?;
+    lines.push(format!("resets at {}", end.format("%Y-%m-%d %H:%M %Z")));
+
+    msg.channel_id.say(&ctx.http, lines.join("\n")).await?;
+    Ok(())
+}
+
+mod sql {
+    use rusqlite::{params, Connection, Result};
+
+    pub fn table(conn: &Connection, id: u64) -> Result<String> {
+        let table = format!("x{}_quota", id);
+        conn.execute(
+            &format!(
+                "CREATE TABLE IF NOT EXISTS \"{}\" (
+                    user CHAR(32),
+                    action CHAR(16),
+                    time INTEGER)",
+                table
+            ),
+            params![],
+        )?;
+
+        Ok(table)
+    }
+
+    pub fn add(conn: &Connection, table: &str, user: u64, action: &str, time: i64) -> Result<()> {
+        conn.execute(
+            &format!(
+                "INSERT INTO \"{}\" (user, action, time) VALUES (?, ?, ?)",
+                table
+            ),
+            params![user.to_string(), action, time],
+        )?;
+
+        Ok(())
+    }
+
+    pub fn count(
+        conn: &Connection,
+        table: &str,
+        user: u64,
+        action: &str,
+        since: i64,
+    ) -> Result<i64> {
+        conn.query_row(
+            &format!(
+                "SELECT count(*) FROM \"{}\" WHERE user=? AND action=? AND time >= ?",
+                table
+            ),
+            params![user.to_string(), action, since],
+            |row| row.get(0),
+        )
+    }
+}
+
+#[cfg(test)]
+pub mod test {
+    use super::*;
+
+    #[test]
+    fn counts_since() {
+        let conn = Connection::open_in_memory().unwrap();
+        let table = sql::table(&conn, 1).unwrap();
+        sql::add(&conn, &table, 2, "addmeme", 100).unwrap();
+        sql::add(&conn, &table, 2, "addmeme", 200).unwrap();
+        sql::add(&conn, &table, 2, "submitmeme", 200).unwrap();
+        sql::add(&conn, &table, 3, "addmeme", 200).unwrap();
+        assert_eq!(sql::count(&conn, &table, 2, "addmeme", 150).unwrap(), 1);
+        assert_eq!(sql::count(&conn, &table, 2, "addmeme", 0).unwrap(), 2);
+    }
+}
@@ -0,0 +1,191 @@
+//! Compacts a guild's meme ids. This only depends on rusqlite so that the
+//! `memebot2-renumber` binary can use it as well.
+
+use rusqlite::{params, Connection, Result, Transaction};
+
+/// Creates the table that maps ids from before a renumbering to the new ones.
+pub fn mapping_table(conn: &Connection, guild: u64) -> Result<String> {
+    let table = format!("x{}_renumbered", guild);
+    conn.execute(
+        &format!(
+            "CREATE TABLE IF NOT EXISTS \"{}\" (
+                old INTEGER UNIQUE,
+                new INTEGER)",
+            table
+        ),
+        params![],
+    )?;
+
+    Ok(table)
+}
+
+/// Forgets where an id came from once a new meme has been given it, so the
+/// new meme isn't mistaken for one that was renumbered. `memes` is the guild's
+/// meme table.
+pub fn reused(conn: &Connection, memes: &str, id: i32) -> Result<()> {
+    let mapping = format!("{}_renumbered", memes.trim_end_matches("_memes"));
+    if exists(conn, &mapping)? {
+        conn.execute(
+            &format!("DELETE FROM \"{}\" WHERE old=?", mapping),
+            params![id],
+        )?;
+    }
+
+    Ok(())
+}
+
+fn exists(conn: &Connection, table: &str) -> Result<bool> {
+    conn.query_row(
+        "SELECT count(*) FROM sqlite_master WHERE type='table' AND name=?",
+        params![table],
+        |row| row.get::<usize, i64>(0),
+    )
+    .map(|x| x > 0)
+}
+
+/// Rewrites the ids in `column` through the `renumber_map` table, setting
+/// ones that aren't in it to NULL. Ids are negated first so that unique
+/// columns can't collide with each other half way through.
+fn remap(tx: &Transaction, table: &str, column: &str, filter: &str) -> Result<()> {
+    tx.execute(
+        &format!(
+            "UPDATE \"{0}\" SET {1}=-{1} WHERE {2}",
+            table, column, filter
+        ),
+        params![],
+    )?;
+    tx.execute(
+        &format!(
+            "UPDATE \"{0}\" SET {1}=(SELECT new FROM renumber_map WHERE old=-{1}) WHERE {2}",
+            table, column, filter
+        ),
+        params![],
+    )?;
+
+    Ok(())
+}
+
+/// Renumbers a guild's memes so that their ids go from 1 up without gaps,
+/// updating everything that refers to them and recording the old ids in the
+/// mapping table. Returns the ids that changed as `(old, new)` pairs.
+pub fn renumber(conn: &mut Connection, guild: u64) -> Result<Vec<(i32, i32)>> {
+    let memes = format!("x{}_memes", guild);
+    let fetches = format!("x{}_fetches", guild);
+    let collections = format!("x{}_collection_memes", guild);
+    let mapping = mapping_table(conn, guild)?;
+    let tx = conn.transaction()?;
+
+    let ids: Vec<i32> = {
+        let mut stmt = tx.prepare(&format!("SELECT id FROM \"{}\" ORDER BY id", memes))?;
+        let iter = stmt.query_map(params![], |row| row.get(0))?;
+        iter.filter_map(|i| i.ok()).collect()
+    };
+    let all: Vec<(i32, i32)> = ids
+        .iter()
+        .enumerate()
+        .map(|(i, x)| (*x, i as i32 + 1))
+        .collect();
+    let changes: Vec<(i32, i32)> = all.iter().filter(|(x, y)| x != y).cloned().collect();
+
+    tx.execute(
+        "CREATE TEMP TABLE IF NOT EXISTS renumber_map (
+            old INTEGER PRIMARY KEY,
+            new INTEGER)",
+        params![],
+    )?;
+    tx.execute("DELETE FROM renumber_map", params![])?;
+    for (old, new) in all.iter() {
+        tx.execute(
+            "INSERT INTO renumber_map (old, new) VALUES (?, ?)",
+            params![old, new],
+        )?;
+    }
+
+    remap(&tx, &memes, "id", "1")?;
+    // fetches of deleted memes are kept for the totals but can't point at an
+    // id that might now belong to a different meme
+    if exists(&tx, &fetches)? {
+        remap(&tx, &fetches, "meme", "1")?;
+    }
+    if exists(&tx, &collections)? {
+        remap(&tx, &collections, "meme", "1")?;
+        tx.execute(
+            &format!("DELETE FROM \"{}\" WHERE meme IS NULL", collections),
+            params![],
+        )?;
+    }
+    if exists(&tx, "shared_memes")? {
+        let filter = format!("guild='{}'", guild);
+        remap(&tx, "shared_memes", "meme", &filter)?;
+        tx.execute(
+            &format!("DELETE FROM shared_memes WHERE meme IS NULL AND {}", filter),
+            params![],
+        )?;
+    }
+
+    // earlier renumberings point at ids that may have just changed
+    remap(&tx, &mapping, "new", "1")?;
+    tx.execute(
+        &format!("DELETE FROM \"{}\" WHERE new IS NULL", mapping),
+        params![],
+    )?;
+    for (old, new) in changes.iter() {
+        tx.execute(
+            &format!(
+                "INSERT OR REPLACE INTO \"{}\" (old, new) VALUES (?, ?)",
+                mapping
+            ),
+            params![old, new],
+        )?;
+    }
+
+    tx.execute(
+        "UPDATE sqlite_sequence SET seq=? WHERE name=?",
+        params![ids.len() as i32, memes],
+    )?;
+    tx.commit()?;
+
+    Ok(changes)
+}
+
+#[cfg(test)]
+pub mod test {
+    use super::*;
+
+    fn mapped(conn: &Connection, old: i32) -> Option<i32> {
+        conn.query_row(
+            "SELECT new FROM x1_renumbered WHERE old=?",
+            params![old],
+            |row| row.get(0),
+        )
+        .ok()
+    }
+
+    #[test]
+    fn compacts_and_forgets_reused_ids() {
+        let mut conn = Connection::open_in_memory().unwrap();
+        conn.execute(
+            "CREATE TABLE x1_memes (id INTEGER PRIMARY KEY AUTOINCREMENT, text TEXT)",
+            params![],
+        )
+        .unwrap();
+        for i in [1, 3, 5].iter() {
+            conn.execute(
+                "INSERT INTO x1_memes (id, text) VALUES (?, 'x')",
+                params![i],
+            )
+            .unwrap();
+        }
+
+        assert_eq!(renumber(&mut conn, 1).unwrap(), vec![(3, 2), (5, 3)]);
+        assert_eq!(mapped(&conn, 5), Some(3));
+
+        for _ in 0..2 {
+            conn.execute("INSERT INTO x1_memes (text) VALUES ('y')", params![])
+                .unwrap();
+            reused(&conn, "x1_memes", conn.last_insert_rowid() as i32).unwrap();
+        }
+        assert_eq!(mapped(&conn, 3), Some(2));
+        assert_eq!(mapped(&conn, 5), None);
+    }
+}
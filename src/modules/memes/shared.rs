@@ -0,0 +1,469 @@
+use rand::seq::SliceRandom;
+use rusqlite::Connection;
+use serenity::client::Context;
+use serenity::framework::standard::macros::{check, command, group};
+use serenity::framework::standard::Args;
+use serenity::framework::standard::CommandResult;
+use serenity::framework::standard::Reason;
+use serenity::model::channel::Message;
+use serenity::model::id::GuildId;
+
+use super::{render, send_embed, valid_name, Meme};
+use crate::misc::paginate;
+
+/// Picks a random meme out of shared entries. Entries are only references to
+/// the meme in the guild that published it, so ones whose meme has since been
+/// deleted there are dropped along the way.
+fn pick(
+    conn: &Connection,
+    mut entries: Vec<(u64, i32)>,
+    nsfw: bool,
+) -> rusqlite::Result<Option<(u64, Meme)>> {
+    entries.shuffle(&mut rand::thread_rng());
+    for (guild, id) in entries {
+        let table = super::sql::table(conn, guild)?;
+        match super::sql::meme_by_id(conn, &table, id) {
+            Ok(x) if x.nsfw && !nsfw => continue,
+            Ok(x) => return Ok(Some((guild, x))),
+            Err(rusqlite::Error::QueryReturnedNoRows) => sql::del_meme_entries(conn, guild, id)?,
+            Err(x) => return Err(x),
+        }
+    }
+
+    Ok(None)
+}
+
+async fn guild_name(ctx: &Context, guild: u64) -> String {
+    GuildId(guild)
+        .name(ctx)
+        .await
+        .unwrap_or_else(|| guild.to_string())
+}
+
+/// Answers `!meme global` and `!meme @collection`. Shared memes are always
+/// sent as an embed so that the guild they came from is credited.
+pub async fn reply(ctx: &Context, msg: &Message, query: &str) -> CommandResult {
+    let conn = Connection::open(crate::config::DB_PATH)?;
+    let guild = *msg.guild_id.unwrap().as_u64();
+    let nsfw = super::nsfw_allowed(ctx, msg).await;
+    sql::tables(&conn)?;
+
+    let res = match query.strip_prefix('@') {
+        Some(name) => {
+            let name = name.to_lowercase();
+            if !sql::can_use(&conn, guild, &name)? {
+                Err(format!(
+                    "this guild isn't subscribed to {}, see `!shared subscribe`",
+                    name
+                ))
+            } else {
+                pick(&conn, sql::collection_entries(&conn, &name)?, nsfw)?
+                    .ok_or_else(|| format!("no memes found in {}", name))
+            }
+        }
+        None => pick(&conn, sql::subscribed_entries(&conn, guild)?, nsfw)?
+            .ok_or_else(|| "no shared memes found, see `!shared list`".to_string()),
+    };
+
+    match res {
+        Ok((origin, x)) => {
+            let text = render(ctx, msg, &x, &[]).await;
+            let origin = guild_name(ctx, origin).await;
+            send_embed(ctx, msg, &x, &text, Some(&origin), None).await?;
+        }
+        Err(x) => drop(msg.channel_id.say(&ctx.http, x).await?),
+    }
+    Ok(())
+}
+
+#[check]
+#[display_in_help(true)]
+async fn shared_flag_p(ctx: &Context, msg: &Message) -> Result<(), Reason> {
+    crate::modules::perms::check_perms(ctx, msg, "p").await
+}
+
+#[command]
+#[aliases(ls)]
+#[only_in("guilds")]
+/// Lists every shared collection. Collections this guild can use are marked
+/// with a `*`.
+async fn list(ctx: &Context, msg: &Message) -> CommandResult {
+    let conn = Connection::open(crate::config::DB_PATH)?;
+    let guild = *msg.guild_id.unwrap().as_u64();
+    sql::tables(&conn)?;
+
+    let mut lines = vec![];
+    for (name, owner, count) in sql::all_collections(&conn)? {
+        lines.push(format!(
+            "{} {} - {} memes from {}",
+            if sql::can_use(&conn, guild, &name)? {
+                "*"
+            } else {
+                " "
+            },
+            name,
+            count,
+            guild_name(ctx, owner).await
+        ));
+    }
+
+    if lines.is_empty() {
+        msg.channel_id
+            .say(&ctx.http, "there are no shared collections")
+            .await?;
+        return Ok(());
+    }
+    paginate(ctx, msg, "shared collections:", &lines).await
+}
+
+#[command]
+#[num_args(1)]
+#[only_in("guilds")]
+#[checks(shared_flag_p)]
+#[usage("<name>")]
+/// Creates a shared collection owned by this guild.
+async fn create(ctx: &Context, msg: &Message, mut args: Args) -> CommandResult {
+    let conn = Connection::open(crate::config::DB_PATH)?;
+    let guild = *msg.guild_id.unwrap().as_u64();
+    let name = args.single::<String>()?.to_lowercase();
+    sql::tables(&conn)?;
+
+    let res = if !valid_name(&name) {
+        "names must be a single word of letters, numbers, `-` or `_` and can't be a number, \
+         `global` or `onthisday`"
+            .to_string()
+    } else if sql::add_collection(&conn, &name, guild)? {
+        format!("created {}, add memes to it with `!shared publish`", name)
+    } else {
+        format!("there is already a collection named {}", name)
+    };
+
+    msg.channel_id.say(&ctx.http, res).await?;
+    Ok(())
+}
+
+#[command("drop")]
+#[num_args(1)]
+#[only_in("guilds")]
+#[checks(shared_flag_p)]
+#[usage("<name>")]
+/// Deletes a shared collection owned by this guild. The memes themselves are
+/// left alone.
+async fn drop_collection(ctx: &Context, msg: &Message, mut args: Args) -> CommandResult {
+    let conn = Connection::open(crate::config::DB_PATH)?;
+    let guild = *msg.guild_id.unwrap().as_u64();
+    let name = args.single::<String>()?.to_lowercase();
+    sql::tables(&conn)?;
+
+    let res = match sql::collection_owner(&conn, &name) {
+        Ok(x) if x == guild => {
+            sql::del_collection(&conn, &name)?;
+            format!("deleted {}", name)
+        }
+        Ok(_) => format!("{} belongs to another guild", name),
+        Err(_) => format!("no collection named {}", name),
+    };
+
+    msg.channel_id.say(&ctx.http, res).await?;
+    Ok(())
+}
+
+#[command]
+#[num_args(2)]
+#[only_in("guilds")]
+#[checks(shared_flag_p)]
+#[usage("<collection> <id>")]
+/// Publishes one of this guild's memes to a collection it owns.
+async fn publish(ctx: &Context, msg: &Message, mut args: Args) -> CommandResult {
+    let conn = Connection::open(crate::config::DB_PATH)?;
+    let guild = *msg.guild_id.unwrap().as_u64();
+    let table = super::sql::table(&conn, guild)?;
+    let name = args.single::<String>()?.to_lowercase();
+    let id: i32 = args.single()?;
+    sql::tables(&conn)?;
+
+    let res = match sql::collection_owner(&conn, &name) {
+        Ok(x) if x != guild => format!("{} belongs to another guild", name),
+        Err(_) => format!("no collection named {}", name),
+        Ok(_) if super::sql::meme_by_id(&conn, &table, id).is_err() => {
+            format!("meme {} not found", id)
+        }
+        Ok(_) => {
+            sql::add_entry(&conn, &name, guild, id)?;
+            format!("meme {} published to {}", id, name)
+        }
+    };
+
+    msg.channel_id.say(&ctx.http, res).await?;
+    Ok(())
+}
+
+#[command]
+#[num_args(2)]
+#[only_in("guilds")]
+#[checks(shared_flag_p)]
+#[usage("<collection> <id>")]
+/// Removes one of this guild's memes from a collection.
+async fn unpublish(ctx: &Context, msg: &Message, mut args: Args) -> CommandResult {
+    let conn = Connection::open(crate::config::DB_PATH)?;
+    let guild = *msg.guild_id.unwrap().as_u64();
+    let name = args.single::<String>()?.to_lowercase();
+    let id: i32 = args.single()?;
+    sql::tables(&conn)?;
+
+    let res = if sql::del_entry(&conn, &name, guild, id)? {
+        format!("meme {} removed from {}", id, name)
+    } else {
+        format!("meme {} isn't in {}", id, name)
+    };
+
+    msg.channel_id.say(&ctx.http, res).await?;
+    Ok(())
+}
+
+#[command]
+#[num_args(1)]
+#[only_in("guilds")]
+#[checks(shared_flag_p)]
+#[usage("<collection>")]
+/// Subscribes this guild to a collection so its memes show up in
+/// `!meme global` and `!meme @collection`.
+async fn subscribe(ctx: &Context, msg: &Message, mut args: Args) -> CommandResult {
+    let conn = Connection::open(crate::config::DB_PATH)?;
+    let guild = *msg.guild_id.unwrap().as_u64();
+    let name = args.single::<String>()?.to_lowercase();
+    sql::tables(&conn)?;
+
+    let res = if sql::collection_owner(&conn, &name).is_err() {
+        format!("no collection named {}", name)
+    } else {
+        sql::subscribe(&conn, guild, &name)?;
+        format!("subscribed to {}", name)
+    };
+
+    msg.channel_id.say(&ctx.http, res).await?;
+    Ok(())
+}
+
+#[command]
+#[num_args(1)]
+#[only_in("guilds")]
+#[checks(shared_flag_p)]
+#[usage("<collection>")]
+/// Unsubscribes this guild from a collection.
+async fn unsubscribe(ctx: &Context, msg: &Message, mut args: Args) -> CommandResult {
+    let conn = Connection::open(crate::config::DB_PATH)?;
+    let guild = *msg.guild_id.unwrap().as_u64();
+    let name = args.single::<String>()?.to_lowercase();
+    sql::tables(&conn)?;
+
+    let res = if sql::unsubscribe(&conn, guild, &name)? {
+        format!("unsubscribed from {}", name)
+    } else {
+        format!("this guild isn't subscribed to {}", name)
+    };
+
+    msg.channel_id.say(&ctx.http, res).await?;
+    Ok(())
+}
+
+#[group]
+#[prefix("shared")]
+#[only_in("guilds")]
+#[commands(
+    list,
+    create,
+    drop_collection,
+    publish,
+    unpublish,
+    subscribe,
+    unsubscribe
+)]
+/// Shares memes between guilds. A guild publishes its memes to collections it
+/// owns and other guilds subscribe to them. Shared memes stay part of the guild
+/// that published them, so deleting one there removes it everywhere.
+///
+/// `!meme global` - gets a random meme from every subscribed collection
+/// `!meme @<collection>` - gets a random meme from one collection
+/// `!shared list` - lists every collection
+///
+/// The following commands require the `p` permission flag.
+///
+/// `!shared create <name>` - creates a collection
+/// `!shared drop <name>` - deletes a collection
+/// `!shared publish <collection> <id>` - adds a meme to a collection
+/// `!shared unpublish <collection> <id>` - removes a meme from a collection
+/// `!shared subscribe <collection>` - subscribes to a collection
+/// `!shared unsubscribe <collection>` - unsubscribes from a collection
+pub struct Shared;
+
+/// Unlike everything else these tables are shared by all guilds.
+pub mod sql {
+    use rusqlite::{params, Connection, Result};
+    use std::str::FromStr;
+
+    pub fn tables(conn: &Connection) -> Result<()> {
+        conn.execute(
+            "CREATE TABLE IF NOT EXISTS shared_collections (
+                name CHAR(32) UNIQUE,
+                owner CHAR(32))",
+            params![],
+        )?;
+        conn.execute(
+            "CREATE TABLE IF NOT EXISTS shared_memes (
+                collection CHAR(32),
+                guild CHAR(32),
+                meme INTEGER,
+                UNIQUE(collection, guild, meme))",
+            params![],
+        )?;
+        conn.execute(
+            "CREATE TABLE IF NOT EXISTS shared_subscriptions (
+                guild CHAR(32),
+                collection CHAR(32),
+                UNIQUE(guild, collection))",
+            params![],
+        )?;
+
+        Ok(())
+    }
+
+    /// Creates a collection, returning false if the name is taken.
+    pub fn add_collection(conn: &Connection, name: &str, owner: u64) -> Result<bool> {
+        let n = conn.execute(
+            "INSERT OR IGNORE INTO shared_collections (name, owner) VALUES (?, ?)",
+            params![name, owner.to_string()],
+        )?;
+
+        Ok(n > 0)
+    }
+
+    pub fn collection_owner(conn: &Connection, name: &str) -> Result<u64> {
+        let owner: String = conn.query_row(
+            "SELECT owner FROM shared_collections WHERE name=?",
+            params![name],
+            |row| row.get(0),
+        )?;
+
+        Ok(u64::from_str(&owner).unwrap_or_default())
+    }
+
+    pub fn del_collection(conn: &Connection, name: &str) -> Result<()> {
+        conn.execute("DELETE FROM shared_collections WHERE name=?", params![name])?;
+        conn.execute("DELETE FROM shared_memes WHERE collection=?", params![name])?;
+        conn.execute(
+            "DELETE FROM shared_subscriptions WHERE collection=?",
+            params![name],
+        )?;
+
+        Ok(())
+    }
+
+    /// Every collection along with its owner and how many memes are in it.
+    pub fn all_collections(conn: &Connection) -> Result<Vec<(String, u64, i64)>> {
+        let mut stmt = conn.prepare(
+            "SELECT c.name, c.owner, count(m.meme) FROM shared_collections c
+                 LEFT JOIN shared_memes m ON m.collection = c.name
+                 GROUP BY c.name ORDER BY c.name",
+        )?;
+        let iter = stmt.query_map(params![], |row| {
+            let owner: String = row.get(1)?;
+            Ok((
+                row.get(0)?,
+                u64::from_str(&owner).unwrap_or_default(),
+                row.get(2)?,
+            ))
+        })?;
+
+        Ok(iter.filter_map(|i| i.ok()).collect())
+    }
+
+    pub fn add_entry(conn: &Connection, collection: &str, guild: u64, meme: i32) -> Result<()> {
+        conn.execute(
+            "INSERT OR IGNORE INTO shared_memes (collection, guild, meme) VALUES (?, ?, ?)",
+            params![collection, guild.to_string(), meme],
+        )?;
+
+        Ok(())
+    }
+
+    /// Removes a meme from a collection, returning whether it was in it.
+    pub fn del_entry(conn: &Connection, collection: &str, guild: u64, meme: i32) -> Result<bool> {
+        let n = conn.execute(
+            "DELETE FROM shared_memes WHERE collection=? AND guild=? AND meme=?",
+            params![collection, guild.to_string(), meme],
+        )?;
+
+        Ok(n > 0)
+    }
+
+    /// Removes a meme from every collection, for when it is deleted.
+    pub fn del_meme_entries(conn: &Connection, guild: u64, meme: i32) -> Result<()> {
+        tables(conn)?;
+        conn.execute(
+            "DELETE FROM shared_memes WHERE guild=? AND meme=?",
+            params![guild.to_string(), meme],
+        )?;
+
+        Ok(())
+    }
+
+    fn entries_from(conn: &Connection, query: &str, param: &str) -> Result<Vec<(u64, i32)>> {
+        let mut stmt = conn.prepare(query)?;
+        let iter = stmt.query_map(params![param], |row| {
+            let guild: String = row.get(0)?;
+            Ok((u64::from_str(&guild).unwrap_or_default(), row.get(1)?))
+        })?;
+
+        Ok(iter.filter_map(|i| i.ok()).collect())
+    }
+
+    pub fn collection_entries(conn: &Connection, collection: &str) -> Result<Vec<(u64, i32)>> {
+        entries_from(
+            conn,
+            "SELECT guild, meme FROM shared_memes WHERE collection=?",
+            collection,
+        )
+    }
+
+    /// Entries in every collection the guild owns or is subscribed to.
+    pub fn subscribed_entries(conn: &Connection, guild: u64) -> Result<Vec<(u64, i32)>> {
+        entries_from(
+            conn,
+            "SELECT DISTINCT guild, meme FROM shared_memes WHERE collection IN (
+                 SELECT collection FROM shared_subscriptions WHERE guild=?1
+                 UNION SELECT name FROM shared_collections WHERE owner=?1)",
+            &guild.to_string(),
+        )
+    }
+
+    /// Whether the guild owns or is subscribed to a collection.
+    pub fn can_use(conn: &Connection, guild: u64, collection: &str) -> Result<bool> {
+        conn.query_row(
+            "SELECT EXISTS (
+                 SELECT 1 FROM shared_subscriptions WHERE guild=?1 AND collection=?2
+                 UNION SELECT 1 FROM shared_collections WHERE owner=?1 AND name=?2)",
+            params![guild.to_string(), collection],
+            |row| row.get(0),
+        )
+    }
+
+    pub fn subscribe(conn: &Connection, guild: u64, collection: &str) -> Result<()> {
+        conn.execute(
+            "INSERT OR IGNORE INTO shared_subscriptions (guild, collection) VALUES (?, ?)",
+            params![guild.to_string(), collection],
+        )?;
+
+        Ok(())
+    }
+
+    /// Unsubscribes a guild, returning whether it was subscribed.
+    pub fn unsubscribe(conn: &Connection, guild: u64, collection: &str) -> Result<bool> {
+        let n = conn.execute(
+            "DELETE FROM shared_subscriptions WHERE guild=? AND collection=?",
+            params![guild.to_string(), collection],
+        )?;
+
+        Ok(n > 0)
+    }
+}
@@ -0,0 +1,160 @@
+use rusqlite::Connection;
+use serenity::client::Context;
+use serenity::framework::standard::macros::command;
+use serenity::framework::standard::CommandResult;
+use serenity::model::channel::Message;
+
+use crate::misc::paginate;
+
+const TOP: usize = 5;
+
+/// Records that a meme was fetched by `!meme`.
+pub fn record(
+    conn: &Connection,
+    guild: u64,
+    meme: i32,
+    channel: u64,
+    user: u64,
+    time: i64,
+) -> rusqlite::Result<()> {
+    let table = sql::table(conn, guild)?;
+    sql::add_fetch(conn, &table, meme, channel, user, time)
+}
+
+pub async fn user_tag(ctx: &Context, id: &str) -> String {
+    match id.parse::<u64>() {
+        Ok(x) => match ctx.cache.user(x).await {
+            Some(x) => x.tag(),
+            None => id.to_string(),
+        },
+        Err(_) => "unknown".to_string(),
+    }
+}
+
+#[command("stats")]
+#[only_in("guilds")]
+/// Shows how the guild's memes are being used.
+async fn show_stats(ctx: &Context, msg: &Message) -> CommandResult {
+    let conn = Connection::open(crate::config::DB_PATH)?;
+    let guild = *msg.guild_id.unwrap().as_u64();
+    let memes = super::sql::table(&conn, guild)?;
+    let table = sql::table(&conn, guild)?;
+
+    let meme_count = sql::count(&conn, &memes)?;
+    let fetch_count = sql::count(&conn, &table)?;
+    let top_memes = sql::top(&conn, &table, "meme", 0..i64::MAX, TOP)?;
+    let top_users = sql::top(&conn, &table, "user", 0..i64::MAX, TOP)?;
+    let curators = sql::top(&conn, &memes, "author", 0..i64::MAX, TOP)?;
+    let months = sql::per_month(&conn, &memes, 12)?;
+
+    let mut lines = vec![
+        format!("{} memes, fetched {} times", meme_count, fetch_count),
+        String::new(),
+        "most requested memes:".to_string(),
+    ];
+    for (id, count) in top_memes {
+        lines.push(format!("  meme {} - {} fetches", id, count));
+    }
+    lines.push(String::new());
+    lines.push("most active requesters:".to_string());
+    for (id, count) in top_users {
+        lines.push(format!(
+            "  {} - {} fetches",
+            user_tag(ctx, &id).await,
+            count
+        ));
+    }
+    lines.push(String::new());
+    lines.push("top curators:".to_string());
+    for (id, count) in curators {
+        lines.push(format!("  {} - {} memes", user_tag(ctx, &id).await, count));
+    }
+    lines.push(String::new());
+    lines.push("memes added per month:".to_string());
+    for (month, count) in months {
+        lines.push(format!("  {} - {}", month, count));
+    }
+
+    paginate(ctx, msg, "", &lines).await
+}
+
+pub mod sql {
+    use rusqlite::{params, Connection, Result};
+
+    pub fn table(conn: &Connection, id: u64) -> Result<String> {
+        let table = format!("x{}_fetches", id);
+        conn.execute(
+            &format!(
+                "CREATE TABLE IF NOT EXISTS \"{}\" (
+                    meme INTEGER,
+                    channel CHAR(32),
+                    user CHAR(32),
+                    time INT)",
+                table
+            ),
+            params![],
+        )?;
+
+        Ok(table)
+    }
+
+    pub fn add_fetch(
+        conn: &Connection,
+        table: &str,
+        meme: i32,
+        channel: u64,
+        user: u64,
+        time: i64,
+    ) -> Result<()> {
+        conn.execute(
+            &format!(
+                "INSERT INTO \"{}\" (meme, channel, user, time) VALUES (?, ?, ?, ?)",
+                table
+            ),
+            params![meme, channel.to_string(), user.to_string(), time],
+        )?;
+
+        Ok(())
+    }
+
+    pub fn count(conn: &Connection, table: &str) -> Result<i64> {
+        conn.query_row(
+            &format!("SELECT count(*) FROM \"{}\"", table),
+            params![],
+            |row| row.get(0),
+        )
+    }
+
+    /// The most common values of a column among rows with a time in `range`,
+    /// along with how often they appear.
+    pub fn top(
+        conn: &Connection,
+        table: &str,
+        column: &str,
+        range: std::ops::Range<i64>,
+        limit: usize,
+    ) -> Result<Vec<(String, i64)>> {
+        let mut stmt = conn.prepare(&format!(
+            "SELECT CAST({1} AS TEXT), count(*) AS n FROM \"{0}\"
+                 WHERE {1} IS NOT NULL AND coalesce(time, 0) >= ? AND coalesce(time, 0) < ?
+                 GROUP BY {1} ORDER BY n DESC LIMIT ?",
+            table, column
+        ))?;
+        let params = params![range.start, range.end, limit as i64];
+        let iter = stmt.query_map(params, |row| Ok((row.get(0)?, row.get(1)?)))?;
+
+        Ok(iter.filter_map(|i| i.ok()).collect())
+    }
+
+    pub fn per_month(conn: &Connection, table: &str, limit: usize) -> Result<Vec<(String, i64)>> {
+        let mut stmt = conn.prepare(&format!(
+            "SELECT strftime('%Y-%m', time, 'unixepoch') AS month, count(*) FROM \"{}\"
+                 WHERE time IS NOT NULL AND time != 0
+                 GROUP BY month ORDER BY month DESC LIMIT ?",
+            table
+        ))?;
+        let iter = stmt.query_map(params![limit as i64], |row| Ok((row.get(0)?, row.get(1)?)))?;
+
+        Ok(iter.filter_map(|i| i.ok()).collect())
+    }
+}
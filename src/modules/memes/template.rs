@@ -0,0 +1,123 @@
+#[derive(PartialEq, Debug)]
+pub enum Token {
+    Text(String),
+    Field(String),
+}
+
+/// Splits a meme into literal text and `{placeholder}` fields. `{{` and `}}`
+/// produce literal braces and a `{` without a matching `}` is kept as is.
+pub fn parse(text: &str) -> Vec<Token> {
+    let mut res = vec![];
+    let mut buf = String::new();
+    let mut chars = text.chars().peekable();
+
+    while let Some(c) = chars.next() {
+        match c {
+            '{' if chars.peek() == Some(&'{') => {
+                chars.next();
+                buf.push('{');
+            }
+            '}' if chars.peek() == Some(&'}') => {
+                chars.next();
+                buf.push('}');
+            }
+            '{' => {
+                let rest: String = chars.clone().collect();
+                match rest.find(['}', '{']) {
+                    Some(x) if rest[x..].starts_with('}') && x > 0 => {
+                        if !buf.is_empty() {
+                            res.push(Token::Text(std::mem::take(&mut buf)));
+                        }
+                        res.push(Token::Field(rest[..x].to_string()));
+                        for _ in 0..rest[..=x].chars().count() {
+                            chars.next();
+                        }
+                    }
+                    _ => buf.push('{'),
+                }
+            }
+            x => buf.push(x),
+        }
+    }
+    if !buf.is_empty() {
+        res.push(Token::Text(buf));
+    }
+
+    res
+}
+
+/// Names of all the fields used in a meme, in order of appearance.
+pub fn fields(text: &str) -> Vec<String> {
+    parse(text)
+        .into_iter()
+        .filter_map(|x| match x {
+            Token::Field(x) => Some(x),
+            Token::Text(_) => None,
+        })
+        .collect()
+}
+
+/// Fills in a meme's fields. Positional fields (`{1}`, `{2}`, ...) are taken
+/// from `args` and everything else is looked up with `lookup`. Fields that
+/// have no value are left in place.
+pub fn expand<F>(text: &str, args: &[String], lookup: F) -> String
+where
+    F: Fn(&str) -> Option<String>,
+{
+    parse(text)
+        .into_iter()
+        .map(|x| match x {
+            Token::Text(x) => x,
+            Token::Field(x) => {
+                let value = match x.parse::<usize>() {
+                    Ok(n) if n > 0 => args.get(n - 1).cloned(),
+                    _ => lookup(&x),
+                };
+                value.unwrap_or_else(|| format!("{{{}}}", x))
+            }
+        })
+        .collect()
+}
+
+#[cfg(test)]
+pub mod test {
+    use super::*;
+
+    fn text(x: &str) -> Token {
+        Token::Text(x.to_string())
+    }
+
+    fn field(x: &str) -> Token {
+        Token::Field(x.to_string())
+    }
+
+    #[test]
+    fn parse_fields() {
+        assert_eq!(
+            parse("hi {user}, {1}!"),
+            vec![
+                text("hi "),
+                field("user"),
+                text(", "),
+                field("1"),
+                text("!")
+            ]
+        );
+    }
+
+    #[test]
+    fn parse_escapes() {
+        assert_eq!(parse("{{user}} {"), vec![text("{user} {")]);
+        assert_eq!(parse("{} {a{b}"), vec![text("{} {a"), field("b")]);
+    }
+
+    #[test]
+    fn expand_values() {
+        let args = vec!["bar".to_string()];
+        let res = expand("{user} says {1} {2} {nope}", &args, |x| match x {
+            "user" => Some("foo".into()),
+            _ => None,
+        });
+        assert_eq!(res, "foo says bar {2} {nope}");
+    }
+}
@@ -1,3 +1,6 @@
+pub mod memeimg;
 pub mod memes;
 pub mod perms;
 pub mod roles;
+pub mod schedule;
+pub mod settings;
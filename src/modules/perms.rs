@@ -8,7 +8,7 @@ use serenity::model::channel::Message;
 use std::collections::HashSet;
 use std::error::Error;
 
-use crate::misc::IdNameMap;
+use crate::misc::{paginate, IdNameMap};
 
 pub async fn check_perms(ctx: &Context, msg: &Message, mode_str: &str) -> Result<(), Reason> {
     // XXX: this nested function is needed as checkresult does not implement
@@ -87,14 +87,11 @@ async fn list(ctx: &Context, msg: &Message) -> CommandResult {
     let table = sql::table(&conn, *msg.guild_id.unwrap().as_u64())?;
     let perms = sql::get_all_perms(&conn, &table)?;
 
-    // FIXME: split responses longer than 2k chars
-    let mut response = "```\n".to_string();
-    for i in perms.iter() {
-        response.push_str(&format!("{} {} {}\n", i.id, i.tag, i.modes.to_string()))
-    }
-    response.push_str("```");
-    msg.channel_id.say(&ctx.http, response).await?;
-    Ok(())
+    let lines: Vec<_> = perms
+        .iter()
+        .map(|i| format!("{} {} {}", i.id, i.tag, i.modes.to_string()))
+        .collect();
+    paginate(ctx, msg, "", &lines).await
 }
 
 #[command]
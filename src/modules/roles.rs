@@ -6,7 +6,7 @@ use serenity::framework::standard::CommandResult;
 use serenity::framework::standard::Reason;
 use serenity::model::channel::Message;
 
-use crate::misc::IdNameMap;
+use crate::misc::{paginate, IdNameMap};
 
 #[check]
 #[display_in_help(true)]
@@ -37,14 +37,11 @@ async fn list(ctx: &Context, msg: &Message) -> CommandResult {
     let table = sql::table(&conn, *msg.guild_id.unwrap().as_u64())?;
     let roles = sql::get_all_roles(&conn, &table)?;
 
-    // FIXME: split responses longer than 2k chars
-    let mut response = "Available Roles: ```\n".to_string();
-    for i in roles.iter() {
-        response.push_str(&format!("{} {}\n", i.id, i.tag))
-    }
-    response.push_str("```");
-    msg.channel_id.say(&ctx.http, response).await?;
-    Ok(())
+    let lines: Vec<_> = roles
+        .iter()
+        .map(|i| format!("{} {}", i.id, i.tag))
+        .collect();
+    paginate(ctx, msg, "Available Roles: ", &lines).await
 }
 
 #[command]
@@ -0,0 +1,214 @@
+use chrono::{DateTime, Datelike, Duration, NaiveDate, TimeZone, Utc, Weekday};
+use chrono_tz::Tz;
+use rusqlite::Connection;
+use serenity::client::Context;
+
+use crate::modules::settings;
+
+#[derive(Clone, Copy)]
+pub enum Period {
+    Daily,
+    Weekly(Weekday),
+}
+
+impl Period {
+    /// The most recent time the job should have run at, given the hour of the
+    /// day it runs at.
+    pub fn last(&self, now: &DateTime<Tz>, hour: u32) -> DateTime<Tz> {
+        // the hour might not exist or happen twice on days the clocks change
+        let tz = now.timezone();
+        let at = |date: NaiveDate| {
+            let local = date.and_hms(hour, 0, 0);
+            tz.from_local_datetime(&local)
+                .earliest()
+                .unwrap_or_else(|| tz.from_utc_datetime(&local))
+        };
+
+        // days are stepped through on the calendar, as they aren't always
+        // 24 hours long
+        let mut date = now.date().naive_local();
+        if let Period::Weekly(day) = self {
+            while date.weekday() != *day {
+                date = date.pred();
+            }
+        }
+        if at(date) > *now {
+            date = match self {
+                Period::Daily => date.pred(),
+                Period::Weekly(_) => date - Duration::weeks(1),
+            };
+        }
+        at(date)
+    }
+
+    pub fn length(&self) -> Duration {
+        match self {
+            Period::Daily => Duration::days(1),
+            Period::Weekly(_) => Duration::weeks(1),
+        }
+    }
+}
+
+/// Checks whether a guild's job is due, returning the time it was due at. A job
+/// is due once per period, at the guild's `post_hour` in its timezone, until
+/// it is marked as done with `mark_done`. The last run is stored in the
+/// database so that restarts neither repeat nor skip a post; a job that was
+/// missed while the bot was down or that failed runs as soon as it can.
+pub fn due(
+    conn: &Connection,
+    guild: u64,
+    job: &str,
+    period: Period,
+) -> rusqlite::Result<Option<DateTime<Tz>>> {
+    let table = sql::table(conn, guild)?;
+    let tz = settings::get_timezone(conn, guild)?;
+    let hour = settings::get_number(conn, guild, "post_hour")? as u32;
+
+    let now = Utc::now().with_timezone(&tz);
+    let last = period.last(&now, hour);
+    let key = last.format("%Y-%m-%d").to_string();
+    if sql::get_last(conn, &table, job).ok().as_ref() == Some(&key) {
+        return Ok(None);
+    }
+
+    Ok(Some(last))
+}
+
+/// Records that a job which was due at `time` has been done.
+pub fn mark_done(
+    conn: &Connection,
+    guild: u64,
+    job: &str,
+    time: &DateTime<Tz>,
+) -> rusqlite::Result<()> {
+    let table = sql::table(conn, guild)?;
+    sql::set_last(conn, &table, job, &time.format("%Y-%m-%d").to_string())?;
+    sql::set_last(
+        conn,
+        &table,
+        &format!("{}:time", job),
+        &time.timestamp().to_string(),
+    )
+}
+
+/// The time a job was due at when it was last done, if it has been done.
+pub fn last_done(conn: &Connection, guild: u64, job: &str) -> rusqlite::Result<Option<i64>> {
+    let table = sql::table(conn, guild)?;
+    match sql::get_last(conn, &table, &format!("{}:time", job)) {
+        Ok(x) => Ok(x.parse().ok()),
+        Err(rusqlite::Error::QueryReturnedNoRows) => Ok(None),
+        Err(x) => Err(x),
+    }
+}
+
+/// Runs scheduled jobs for every guild the bot is in, once a minute. Jobs
+/// are run independently so one failing doesn't hold up the others.
+pub async fn run(ctx: Context) {
+    loop {
+        for guild in ctx.cache.guilds().await {
+            let guild = *guild.as_u64();
+            if let Err(x) = crate::modules::memes::onthisday::scheduled(&ctx, guild).await {
+                println!("error posting onthisday for {}: {}", guild, x);
+            }
+            if let Err(x) = crate::modules::memes::digest::scheduled(&ctx, guild).await {
+                println!("error posting digest for {}: {}", guild, x);
+            }
+        }
+        tokio::time::sleep(std::time::Duration::from_secs(60)).await;
+    }
+}
+
+mod sql {
+    use rusqlite::{params, Connection, Result};
+
+    pub fn table(conn: &Connection, id: u64) -> Result<String> {
+        let table = format!("x{}_schedule", id);
+        conn.execute(
+            &format!(
+                "CREATE TABLE IF NOT EXISTS \"{}\" (
+                    job CHAR(32) UNIQUE,
+                    last CHAR(32))",
+                table
+            ),
+            params![],
+        )?;
+
+        Ok(table)
+    }
+
+    pub fn get_last(conn: &Connection, table: &str, job: &str) -> Result<String> {
+        conn.query_row(
+            &format!("SELECT last FROM \"{}\" WHERE job=?", table),
+            params![job],
+            |row| row.get(0),
+        )
+    }
+
+    pub fn set_last(conn: &Connection, table: &str, job: &str, last: &str) -> Result<()> {
+        conn.execute(
+            &format!(
+                "INSERT INTO \"{}\" (job, last) VALUES (?, ?)
+                 ON CONFLICT(job) DO UPDATE SET last=excluded.last",
+                table
+            ),
+            params![job, last],
+        )?;
+
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+pub mod test {
+    use super::*;
+
+    fn at(day: u32, hour: u32) -> DateTime<Tz> {
+        Tz::UTC.ymd(2021, 6, day).and_hms(hour, 30, 0)
+    }
+
+    #[test]
+    fn daily() {
+        assert_eq!(
+            Period::Daily.last(&at(2, 13), 12),
+            Tz::UTC.ymd(2021, 6, 2).and_hms(12, 0, 0)
+        );
+        assert_eq!(
+            Period::Daily.last(&at(2, 11), 12),
+            Tz::UTC.ymd(2021, 6, 1).and_hms(12, 0, 0)
+        );
+    }
+
+    #[test]
+    fn weekly() {
+        // 2021-06-02 was a wednesday
+        let period = Period::Weekly(Weekday::Mon);
+        assert_eq!(
+            period.last(&at(2, 13), 12),
+            Tz::UTC.ymd(2021, 5, 31).and_hms(12, 0, 0)
+        );
+        assert_eq!(
+            period.last(&at(7, 11), 12),
+            Tz::UTC.ymd(2021, 5, 31).and_hms(12, 0, 0)
+        );
+        assert_eq!(
+            period.last(&at(7, 13), 12),
+            Tz::UTC.ymd(2021, 6, 7).and_hms(12, 0, 0)
+        );
+
+        // the clocks went forward on 2021-03-28, a sunday
+        let tz = chrono_tz::Europe::London;
+        let now = tz.ymd(2021, 3, 31).and_hms(10, 0, 0);
+        assert_eq!(
+            Period::Weekly(Weekday::Sun).last(&now, 0),
+            tz.ymd(2021, 3, 28).and_hms(0, 0, 0)
+        );
+        assert_eq!(
+            Period::Weekly(Weekday::Sat).last(&now, 12),
+            tz.ymd(2021, 3, 27).and_hms(12, 0, 0)
+        );
+        assert_eq!(
+            Period::Daily.last(&tz.ymd(2021, 3, 28).and_hms(10, 0, 0), 12),
+            tz.ymd(2021, 3, 27).and_hms(12, 0, 0)
+        );
+    }
+}
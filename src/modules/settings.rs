@@ -0,0 +1,365 @@
+use chrono::Weekday;
+use chrono_tz::Tz;
+use rusqlite::Connection;
+use serenity::client::Context;
+use serenity::framework::standard::macros::{check, command, group};
+use serenity::framework::standard::Args;
+use serenity::framework::standard::CommandResult;
+use serenity::framework::standard::Reason;
+use serenity::model::channel::{Channel, Message};
+use serenity::model::id::{ChannelId, GuildId};
+
+use crate::modules::schedule::Period;
+
+#[check]
+#[display_in_help(true)]
+async fn settings_flag_p(ctx: &Context, msg: &Message) -> Result<(), Reason> {
+    crate::modules::perms::check_perms(ctx, msg, "p").await
+}
+
+pub enum Kind {
+    Bool,
+    Number {
+        min: i64,
+        max: i64,
+    },
+    Channel,
+    Timezone,
+    /// `daily` or the day of the week something happens on.
+    Period,
+}
+
+pub struct Setting {
+    pub key: &'static str,
+    pub kind: Kind,
+    pub default: &'static str,
+    pub help: &'static str,
+}
+
+pub const SETTINGS: &[Setting] = &[
+    Setting {
+        key: "meme_embed",
+        kind: Kind::Bool,
+        default: "off",
+        help: "render !meme results as an embed instead of plain text",
+    },
+    Setting {
+        key: "markov_order",
+        kind: Kind::Number { min: 1, max: 4 },
+        default: "2",
+        help: "number of words !markov looks back when picking the next word",
+    },
+    Setting {
+        key: "timezone",
+        kind: Kind::Timezone,
+        default: "UTC",
+        help: "timezone used for dates and scheduled posts, e.g. Europe/London",
+    },
+    Setting {
+        key: "post_hour",
+        kind: Kind::Number { min: 0, max: 23 },
+        default: "12",
+        help: "hour of the day scheduled posts are made at",
+    },
+    Setting {
+        key: "onthisday_channel",
+        kind: Kind::Channel,
+        default: "off",
+        help: "channel to post memes added on this day in previous years to",
+    },
+    Setting {
+        key: "digest_channel",
+        kind: Kind::Channel,
+        default: "off",
+        help: "channel to post a digest of new and popular memes to",
+    },
+    Setting {
+        key: "digest_day",
+        kind: Kind::Period,
+        default: "mon",
+        help: "day of the week the digest is posted on, or daily",
+    },
+    Setting {
+        key: "game_time",
+        kind: Kind::Number { min: 5, max: 300 },
+        default: "30",
+        help: "seconds players have to answer a !memegame round",
+    },
+    Setting {
+        key: "channel_fallback",
+        kind: Kind::Bool,
+        default: "on",
+        help: "let !meme use the guild's memes when a channel's own memes have no match",
+    },
+    Setting {
+        key: "inline_memes",
+        kind: Kind::Bool,
+        default: "off",
+        help: "answer [[id]] and [[name]] in normal messages with the meme",
+    },
+    Setting {
+        key: "inline_max",
+        kind: Kind::Number { min: 1, max: 10 },
+        default: "3",
+        help: "most memes answered for a single message with inline_memes",
+    },
+    Setting {
+        key: "capture_pins",
+        kind: Kind::Bool,
+        default: "off",
+        help: "add newly pinned messages as memes automatically",
+    },
+    Setting {
+        key: "min_length",
+        kind: Kind::Number { min: 1, max: 2000 },
+        default: "1",
+        help: "shortest meme allowed by the content policy, in characters",
+    },
+    Setting {
+        key: "max_length",
+        kind: Kind::Number { min: 1, max: 2000 },
+        default: "500",
+        help: "longest meme allowed by the content policy, in characters",
+    },
+    Setting {
+        key: "addmeme_quota",
+        kind: Kind::Number { min: 0, max: 1000 },
+        default: "0",
+        help: "memes a member can add with !addmeme per quota period, 0 for no limit",
+    },
+    Setting {
+        key: "submit_quota",
+        kind: Kind::Number { min: 0, max: 1000 },
+        default: "0",
+        help: "memes a member can submit with !submitmeme per quota period, 0 for no limit",
+    },
+    Setting {
+        key: "quota_period",
+        kind: Kind::Period,
+        default: "daily",
+        help: "how often quotas reset, daily or on a day of the week",
+    },
+];
+
+impl Setting {
+    pub fn find(key: &str) -> Option<&'static Setting> {
+        SETTINGS.iter().find(|x| x.key == key)
+    }
+
+    /// Normalizes a user supplied value, returning `None` if it is not valid
+    /// for this setting.
+    pub fn parse(&self, value: &str) -> Option<String> {
+        match self.kind {
+            Kind::Bool => match value.to_lowercase().as_str() {
+                "on" | "true" | "yes" | "1" => Some("on".into()),
+                "off" | "false" | "no" | "0" => Some("off".into()),
+                _ => None,
+            },
+            Kind::Number { min, max } => match value.parse::<i64>() {
+                Ok(x) if (min..=max).contains(&x) => Some(x.to_string()),
+                _ => None,
+            },
+            Kind::Channel => match value.to_lowercase().as_str() {
+                "off" | "none" => Some("off".into()),
+                x => x
+                    .trim_start_matches("<#")
+                    .trim_end_matches('>')
+                    .parse::<u64>()
+                    .ok()
+                    .map(|x| x.to_string()),
+            },
+            Kind::Timezone => value.parse::<Tz>().ok().map(|x| x.name().to_string()),
+            Kind::Period => match value.to_lowercase().as_str() {
+                "daily" => Some("daily".into()),
+                x => x
+                    .parse::<Weekday>()
+                    .ok()
+                    .map(|x| format!("{:?}", x).to_lowercase()),
+            },
+        }
+    }
+}
+
+/// Gets the value of a setting for a guild, falling back to its default.
+pub fn get(conn: &Connection, guild: u64, key: &str) -> rusqlite::Result<String> {
+    let table = sql::table(conn, guild)?;
+    match sql::get_setting(conn, &table, key) {
+        Ok(x) => Ok(x),
+        Err(rusqlite::Error::QueryReturnedNoRows) => Ok(Setting::find(key)
+            .map(|x| x.default.to_string())
+            .unwrap_or_default()),
+        Err(x) => Err(x),
+    }
+}
+
+pub fn get_bool(conn: &Connection, guild: u64, key: &str) -> rusqlite::Result<bool> {
+    Ok(get(conn, guild, key)? == "on")
+}
+
+pub fn get_number(conn: &Connection, guild: u64, key: &str) -> rusqlite::Result<i64> {
+    Ok(get(conn, guild, key)?.parse().unwrap_or_default())
+}
+
+/// Gets a channel setting, returning `None` if it is turned off.
+pub fn get_channel(conn: &Connection, guild: u64, key: &str) -> rusqlite::Result<Option<u64>> {
+    Ok(get(conn, guild, key)?.parse().ok())
+}
+
+pub fn get_period(conn: &Connection, guild: u64, key: &str) -> rusqlite::Result<Period> {
+    Ok(match get(conn, guild, key)?.parse::<Weekday>() {
+        Ok(x) => Period::Weekly(x),
+        Err(_) => Period::Daily,
+    })
+}
+
+pub fn get_timezone(conn: &Connection, guild: u64) -> rusqlite::Result<Tz> {
+    Ok(get(conn, guild, "timezone")?.parse().unwrap_or(Tz::UTC))
+}
+
+#[command]
+#[aliases(ls)]
+#[only_in("guilds")]
+/// Lists all settings and their current values
+async fn list(ctx: &Context, msg: &Message) -> CommandResult {
+    let conn = Connection::open(crate::config::DB_PATH)?;
+    let guild = *msg.guild_id.unwrap().as_u64();
+
+    let mut response = "```\n".to_string();
+    for i in SETTINGS.iter() {
+        response.push_str(&format!(
+            "{} = {} ({})\n",
+            i.key,
+            get(&conn, guild, i.key)?,
+            i.help
+        ));
+    }
+    response.push_str("```");
+    msg.channel_id.say(&ctx.http, response).await?;
+    Ok(())
+}
+
+/// Whether a channel setting's value is off or a channel in the guild, so
+/// scheduled posts can't be sent to other guilds.
+async fn in_guild(ctx: &Context, guild: GuildId, value: &str) -> bool {
+    let channel = match value.parse() {
+        Ok(x) => ChannelId(x).to_channel_cached(ctx).await,
+        Err(_) => return value == "off",
+    };
+    matches!(channel, Some(Channel::Guild(x)) if x.guild_id == guild)
+}
+
+#[command]
+#[num_args(2)]
+#[only_in("guilds")]
+#[usage("<key> <value>")]
+/// Changes a setting.
+async fn set(ctx: &Context, msg: &Message, mut args: Args) -> CommandResult {
+    let conn = Connection::open(crate::config::DB_PATH)?;
+    let guild = msg.guild_id.unwrap();
+    let table = sql::table(&conn, *guild.as_u64())?;
+
+    let key: String = args.single()?;
+    let value: String = args.single_quoted()?;
+
+    let res = match Setting::find(&key) {
+        Some(setting) => match setting.parse(&value) {
+            Some(x) if matches!(setting.kind, Kind::Channel) && !in_guild(ctx, guild, &x).await => {
+                format!("channel {} not found", value)
+            }
+            Some(x) => {
+                sql::set_setting(&conn, &table, &key, &x)?;
+                format!("{} set to {}", key, x)
+            }
+            None => format!("`{}` is not a valid value for {}", value, key),
+        },
+        None => format!("no setting named `{}`", key),
+    };
+
+    msg.channel_id.say(&ctx.http, res).await?;
+    Ok(())
+}
+
+#[command]
+#[num_args(1)]
+#[only_in("guilds")]
+#[usage("<key>")]
+/// Resets a setting to its default value.
+async fn reset(ctx: &Context, msg: &Message, mut args: Args) -> CommandResult {
+    let conn = Connection::open(crate::config::DB_PATH)?;
+    let table = sql::table(&conn, *msg.guild_id.unwrap().as_u64())?;
+
+    let key: String = args.single()?;
+    let res = match Setting::find(&key) {
+        Some(setting) => {
+            sql::del_setting(&conn, &table, &key)?;
+            format!("{} reset to {}", key, setting.default)
+        }
+        None => format!("no setting named `{}`", key),
+    };
+
+    msg.channel_id.say(&ctx.http, res).await?;
+    Ok(())
+}
+
+#[group]
+#[prefix("settings")]
+#[only_in("guilds")]
+#[commands(list, set, reset)]
+#[checks(settings_flag_p)]
+/// The settings group contains per-guild configuration for the other modules.
+///
+/// All commands in this group require the `p` permission flag.
+///
+/// `!settings list` - lists all settings with their current values
+/// `!settings set <key> <value>` - changes a setting
+/// `!settings reset <key>` - restores a setting to its default
+pub struct Settings;
+
+mod sql {
+    use rusqlite::{params, Connection, Result};
+
+    pub fn table(conn: &Connection, id: u64) -> Result<String> {
+        let table = format!("x{}_settings", id);
+        conn.execute(
+            &format!(
+                "CREATE TABLE IF NOT EXISTS \"{}\" (
+                    key CHAR(32) UNIQUE,
+                    value TEXT)",
+                table
+            ),
+            params![],
+        )?;
+
+        Ok(table)
+    }
+
+    pub fn get_setting(conn: &Connection, table: &str, key: &str) -> Result<String> {
+        conn.query_row(
+            &format!("SELECT value FROM \"{}\" WHERE key=?", table),
+            params![key],
+            |row| row.get(0),
+        )
+    }
+
+    pub fn set_setting(conn: &Connection, table: &str, key: &str, value: &str) -> Result<()> {
+        conn.execute(
+            &format!(
+                "INSERT INTO \"{}\" (key, value) VALUES (?, ?)
+                 ON CONFLICT(key) DO UPDATE SET value=excluded.value",
+                table
+            ),
+            params![key, value],
+        )?;
+
+        Ok(())
+    }
+
+    pub fn del_setting(conn: &Connection, table: &str, key: &str) -> Result<()> {
+        conn.execute(
+            &format!("DELETE FROM \"{}\" WHERE key=?", table),
+            params![key],
+        )?;
+
+        Ok(())
+    }
+}